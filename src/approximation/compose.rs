@@ -0,0 +1,50 @@
+use num_traits::One;
+use std::ops::MulAssign;
+
+use approximation::ApproximationStrategy;
+use recognisable::Instruction;
+use recognisable::automaton::Automaton;
+
+/// `ApproximationStrategy` that applies the strategy `first` and then the strategy `second`,
+/// e.g. `Compose::new(TTSElement::new(), rlb)` approximates a `TreeStackAutomaton` by a
+/// relabelled `PushDownAutomaton`.
+/// The `ApproximationInstance` of a `Compose` maps each transition of the coarsest automaton
+/// directly to the transitions of the finest automaton that it approximates through both
+/// strategies, so that both levels can be used as a single level for coarse-to-fine recognition.
+#[derive(Clone, Debug)]
+pub struct Compose<S1, S2> {
+    pub first: S1,
+    pub second: S2,
+}
+
+impl<S1, S2> Compose<S1, S2> {
+    pub fn new(first: S1, second: S2) -> Self {
+        Compose { first, second }
+    }
+}
+
+impl<S1, S2, T, W> ApproximationStrategy<T, W> for Compose<S1, S2>
+    where S1: ApproximationStrategy<T, W>,
+          S2: ApproximationStrategy<T, W, I1=S1::I2, A1=S1::A2>,
+          S1::I1: Clone + Eq + Instruction + Ord,
+          S1::I2: Clone + Eq + Instruction + Ord,
+          S2::I2: Clone + Eq + Instruction + Ord,
+          S1::A1: Automaton<T, W, I=S1::I1>,
+          S1::A2: Automaton<T, W, I=S1::I2>,
+          S2::A2: Automaton<T, W, I=S2::I2>,
+          T: Clone + Eq + Ord,
+          W: Clone + MulAssign + One + Ord,
+{
+    type I1 = S1::I1;
+    type I2 = S2::I2;
+    type A1 = S1::A1;
+    type A2 = S2::A2;
+
+    fn approximate_storage(&self, s: <S1::I1 as Instruction>::Storage) -> <S2::I2 as Instruction>::Storage {
+        self.second.approximate_storage(self.first.approximate_storage(s))
+    }
+
+    fn approximate_instruction(&self, instruction: &S1::I1) -> S2::I2 {
+        self.second.approximate_instruction(&self.first.approximate_instruction(instruction))
+    }
+}
@@ -1,10 +1,10 @@
 use nom::{IResult, is_space};
 use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::Entry;
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
 use std::iter::FromIterator;
-use std::str::FromStr;
+use std::str::{FromStr, from_utf8};
 
 use util::parsing::*;
 
@@ -90,6 +90,220 @@ impl<A, B> EquivalenceRelation<A, B>
             Some(e) => e.clone(),
         }
     }
+
+    /// The class of all values that are not in any other class.
+    pub fn default_class(&self) -> &B {
+        &self.default
+    }
+
+    /// Returns the `values` that fall through to the default class, e.g. the nonterminals of a
+    /// grammar that were forgotten in a classes file.
+    pub fn unclassified<'a, It>(&self, values: It) -> Vec<&'a A>
+        where It: IntoIterator<Item=&'a A>,
+              A: 'a,
+    {
+        values.into_iter().filter(|value| !self.map.contains_key(value)).collect()
+    }
+}
+
+impl<A, B> EquivalenceRelation<A, B>
+    where A: Display + Eq + Hash,
+          B: Display,
+{
+    /// Returns the labels and values that can not be written such that `from_str` reads them
+    /// again (cf. `util::parsing::is_writable_token`).
+    pub fn unwritable(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = self.map.iter()
+            .flat_map(|(value, class)| vec![value.to_string(), class.to_string()])
+            .chain(Some(self.default.to_string()))
+            .filter(|token| !is_writable_token(token))
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+        tokens
+    }
+}
+
+/// Writes one class `label [a, b, …]` per line (in the syntax of `from_str`), followed by the
+/// default class `label *`.
+/// Labels and values are quoted if they could not be read otherwise (cf. `quote_token`), hence
+/// the result can only be read again if `unwritable` is empty.
+impl<A, B> Display for EquivalenceRelation<A, B>
+    where A: Display + Eq + Hash,
+          B: Display + Eq + Hash,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut classes: HashMap<&B, Vec<String>> = HashMap::new();
+        for (value, class) in &self.map {
+            classes.entry(class).or_insert_with(Vec::new).push(quote_token(&value.to_string()));
+        }
+
+        let mut lines: Vec<(String, Vec<String>)> = classes
+            .into_iter()
+            .map(|(class, mut values)| {
+                values.sort();
+                (quote_token(&class.to_string()), values)
+            })
+            .collect();
+        lines.sort();
+
+        for (class, values) in lines {
+            writeln!(f, "{} [{}]", class, values.join(", "))?;
+        }
+        write!(f, "{} *", quote_token(&self.default.to_string()))
+    }
+}
+
+/// A chain of `EquivalenceRelation`s, from the coarsest to the finest, in which every class of a
+/// level is contained in a class of the previous (coarser) level.
+/// Relabelling with each level in turn yields a sequence of progressively finer automata for
+/// coarse-to-fine recognition, cf. `recognisable::coarse_to_fine::relabelling_recogniser`.
+#[derive(Clone, Debug)]
+pub struct RelationHierarchy<A, B>
+    where A: Eq + Hash,
+          B: Eq + Hash,
+{
+    levels: Vec<EquivalenceRelation<A, B>>,
+    coarsenings: Vec<EquivalenceRelation<B, B>>,
+}
+
+impl<A, B> RelationHierarchy<A, B>
+    where A: Debug + Eq + Hash,
+          B: Clone + Debug + Eq + Hash,
+{
+    /// Checks that each of the `levels` (from the coarsest to the finest) refines the previous one.
+    pub fn new(levels: Vec<EquivalenceRelation<A, B>>) -> Result<Self, String> {
+        if levels.is_empty() {
+            return Err(String::from("A hierarchy of equivalence relations needs at least one level!"));
+        }
+
+        let mut coarsenings = Vec::new();
+        for i in 1..levels.len() {
+            coarsenings.push(coarsening(&levels[i - 1], &levels[i], i)?);
+        }
+
+        Ok(RelationHierarchy { levels, coarsenings })
+    }
+
+    /// The levels of the hierarchy, from the coarsest to the finest.
+    pub fn levels(&self) -> &[EquivalenceRelation<A, B>] {
+        &self.levels
+    }
+
+    /// Maps the classes of level `i + 1` to the classes of level `i` that contain them.
+    pub fn coarsening(&self, i: usize) -> &EquivalenceRelation<B, B> {
+        &self.coarsenings[i]
+    }
+}
+
+impl<A> RelationHierarchy<A, A>
+    where A: Clone + Debug + Eq + Hash,
+{
+    /// Returns the relations that relabel the elements with the classes of the finest level, and
+    /// then each of these classes with the class of the next coarser level.
+    pub fn relabellings(&self) -> Vec<EquivalenceRelation<A, A>> {
+        let mut relabellings = vec![self.levels[self.levels.len() - 1].clone()];
+        relabellings.extend(self.coarsenings.iter().rev().cloned());
+        relabellings
+    }
+}
+
+/// Returns the relation that maps the classes of `fine` to the classes of `coarse` that contain
+/// them, or an error if `fine` does not refine `coarse`.
+/// The level of `fine` in the hierarchy is only used for error messages.
+fn coarsening<A, B>(coarse: &EquivalenceRelation<A, B>, fine: &EquivalenceRelation<A, B>, level: usize)
+                    -> Result<EquivalenceRelation<B, B>, String>
+    where A: Debug + Eq + Hash,
+          B: Clone + Debug + Eq + Hash,
+{
+    let mut map: HashMap<B, B> = HashMap::new();
+    for (value, fine_class) in &fine.map {
+        let coarse_class = coarse.project(value);
+        match map.entry(fine_class.clone()) {
+            Entry::Vacant(v) => {
+                v.insert(coarse_class);
+            },
+            Entry::Occupied(o) => {
+                if *o.get() != coarse_class {
+                    return Err(format!(
+                        "The class {:?} of level {} is not contained in a class of level {}!",
+                        fine_class, level + 1, level
+                    ));
+                }
+            },
+        }
+    }
+
+    for value in coarse.map.keys() {
+        if !fine.map.contains_key(value) {
+            return Err(format!(
+                "The default class of level {} is not contained in a class of level {}, as it contains {:?}!",
+                level + 1, level, value
+            ));
+        }
+    }
+
+    map.retain(|_, coarse_class| *coarse_class != coarse.default);
+    Ok(EquivalenceRelation { map, default: coarse.default.clone() })
+}
+
+/// Reads the levels of the hierarchy from the coarsest to the finest, each introduced by a line
+/// `level:` and given in the syntax of `EquivalenceRelation::from_str`.
+/// An input without a `level:` line is read as a hierarchy with a single level.
+impl<A, B> FromStr for RelationHierarchy<A, B>
+    where A: Clone + Debug + Eq + Hash + FromStr,
+          A::Err: Debug,
+          B: Clone + Debug + Eq + Hash + FromStr,
+          B::Err: Debug,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut blocks: Vec<Vec<(usize, &str)>> = Vec::new();
+        let mut preamble = Vec::new();
+
+        for (i, l) in s.lines().enumerate() {
+            let l = l.trim();
+            if l.is_empty() || l.starts_with('%') {
+                continue;
+            }
+            if l.starts_with("level:") {
+                blocks.push(Vec::new());
+            } else {
+                match blocks.last_mut() {
+                    Some(block) => block.push((i + 1, l)),
+                    None => preamble.push((i + 1, l)),
+                }
+            }
+        }
+
+        if blocks.is_empty() {
+            blocks.push(preamble);
+        } else if let Some(&(line, l)) = preamble.first() {
+            return Err(format!("line {}: Expected \'level:\' before \'{}\'", line, l));
+        }
+
+        let mut levels = Vec::new();
+        for block in blocks {
+            levels.push(relation_from_lines(&block)?);
+        }
+        RelationHierarchy::new(levels)
+    }
+}
+
+impl<A, B> Display for RelationHierarchy<A, B>
+    where A: Display + Eq + Hash,
+          B: Display + Eq + Hash,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, level) in self.levels.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "level:\n{}", level)?;
+        }
+        Ok(())
+    }
 }
 
 impl<A, B> From<Vec<EquivalenceClass<A, B>>> for EquivalenceRelation<A, B>
@@ -114,38 +328,100 @@ impl<A, B> From<Vec<EquivalenceClass<A, B>>> for EquivalenceRelation<A, B>
     }
 }
 
+/// Reads one class per line, either `label [a, b, …]` or the default class `label *`.
+/// Labels and values are tokens in the sense of `util::parsing::parse_token`, i.e. they need to
+/// be quoted if they contain spaces or one of the symbols `"-→,;)]`.
+/// Empty lines and lines starting with `%` are ignored, and a class may be followed by a `%`
+/// comment.
+/// Errors are reported with the number of the offending line.
 impl<A, B> FromStr for EquivalenceRelation<A, B>
-    where A: Clone + Eq + Hash + FromStr,
+    where A: Clone + Debug + Eq + Hash + FromStr,
           A::Err: Debug,
-          B: Clone + Eq + Hash + FromStr,
+          B: Clone + Debug + Eq + Hash + FromStr,
           B::Err: Debug,
 {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut map = HashMap::new();
-        let mut default = None;
+        let lines: Vec<(usize, &str)> = s
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|&(_, l)| !l.is_empty() && !l.starts_with('%'))
+            .collect();
+        relation_from_lines(&lines)
+    }
+}
 
-        for l in s.lines() {
-            if !l.is_empty() {
-                match l.trim().parse()? {
-                    EquivalenceClass { label, set: Some(elements) } => {
-                        map.insert(label, elements);
-                    },
-                    EquivalenceClass { label, set: None } => {
-                        default = Some(label);
-                    },
+/// Reads an `EquivalenceRelation` from the given (non-empty, non-comment) `lines`, each with its
+/// line number.
+fn relation_from_lines<A, B>(lines: &[(usize, &str)]) -> Result<EquivalenceRelation<A, B>, String>
+    where A: Clone + Debug + Eq + Hash + FromStr,
+          A::Err: Debug,
+          B: Clone + Debug + Eq + Hash + FromStr,
+          B::Err: Debug,
+{
+    let mut map = HashMap::new();
+    let mut class_lines: HashMap<B, usize> = HashMap::new();
+    let mut value_lines: HashMap<A, usize> = HashMap::new();
+    let mut default: Option<(B, usize)> = None;
+
+    for &(line, l) in lines {
+        let class = match parse_class(l.as_bytes()) {
+            IResult::Done(rest, class) => {
+                let rest = from_utf8(rest).unwrap_or("").trim();
+                if !rest.is_empty() && !rest.starts_with('%') {
+                    return Err(format!("line {}: Unexpected \'{}\' after the class", line, rest));
+                }
+                class
+            },
+            _ => return Err(format!("line {}: Could not parse \'{}\'", line, l)),
+        };
+
+        match class {
+            EquivalenceClass { label, set: Some(elements) } => {
+                if let Some(other_line) = class_lines.get(&label) {
+                    return Err(format!(
+                        "line {}: The class {:?} was already defined in line {}",
+                        line, label, other_line
+                    ));
+                }
+                for element in &elements {
+                    if let Some(other_line) = value_lines.get(element) {
+                        return Err(format!(
+                            "line {}: {:?} is already contained in the class of line {}",
+                            line, element, other_line
+                        ));
+                    }
                 }
-            }
-        }
 
-        if let Some(label) = default {
-            if let Ok(relation) = EquivalenceRelation::new_safe(map, label) {
-                return Ok(relation);
-            }
+                value_lines.extend(elements.iter().map(|element| (element.clone(), line)));
+                class_lines.insert(label.clone(), line);
+                map.insert(label, elements);
+            },
+            EquivalenceClass { label, set: None } => {
+                if let Some((_, other_line)) = default {
+                    return Err(format!(
+                        "line {}: The default class was already defined in line {}",
+                        line, other_line
+                    ));
+                }
+                default = Some((label, line));
+            },
         }
+    }
 
-        Err(format!("Could not parse {}", s))
+    match default {
+        Some((label, line)) => {
+            if let Some(other_line) = class_lines.get(&label) {
+                return Err(format!(
+                    "line {}: The default class {:?} was already defined in line {}",
+                    line, label, other_line
+                ));
+            }
+            EquivalenceRelation::new_safe(map, label)
+        },
+        None => Err(String::from("There is no default class (i.e. a line \'label *\')")),
     }
 }
 
@@ -346,6 +622,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_equivalence_relation_from_str_comments_and_quotes() {
+        let rel: EquivalenceRelation<String, String> = "% nouns\n\
+                                                        \"noun phrase\" [NP, \"NP-SBJ\"] % subjects, too\n\
+                                                        \n\
+                                                        \"[other]\" *".parse().unwrap();
+        assert_eq!("noun phrase", rel.project(&String::from("NP-SBJ")));
+        assert_eq!("[other]", rel.project(&String::from("VP")));
+        assert_eq!("[other]", rel.default_class());
+        assert_eq!(rel, rel.to_string().parse().unwrap());
+
+        let nonterminals = vec![String::from("NP"), String::from("VP")];
+        assert_eq!(vec![&nonterminals[1]], rel.unclassified(&nonterminals));
+    }
+
+    #[test]
+    fn test_equivalence_relation_from_str_errors() {
+        let illegal_inputs = vec![
+            ("0 [0, 1]\n% comment\n1 [1, 2]\n2 *", "line 3:"),
+            ("0 [0, 1]\n0 [2]\n2 *", "line 2:"),
+            ("0 [0, 1] 1 [2]\n2 *", "line 1:"),
+            ("0 [0, 1]\n2 *\n3 *", "line 3:"),
+            ("0 [0, 1]\n\n0 *", "line 3:"),
+            ("0 [0, 1\n2 *", "line 1:"),
+            ("0 [0, 1]", "no default class"),
+        ];
+
+        for (illegal_input, control_error) in illegal_inputs {
+            let error = EquivalenceRelation::<u8, u8>::from_str(illegal_input).unwrap_err();
+            assert!(error.contains(control_error), "unexpected error \'{}\' for \'{}\'", error, illegal_input);
+        }
+    }
+
+    #[test]
+    fn test_equivalence_relation_display() {
+        let rel: EquivalenceRelation<u8, u8> = String::from("1 [4, 2]\n0 [1, 0]\n2 *").parse().unwrap();
+        assert_eq!("0 [0, 1]\n1 [2, 4]\n2 *", rel.to_string());
+        assert_eq!(rel, rel.to_string().parse().unwrap());
+        assert!(rel.unwritable().is_empty());
+
+        let mut map = HashMap::new();
+        map.insert(String::from("NP\\\"x"), vec![String::from("NP\"-1")].into_iter().collect());
+        let rel = EquivalenceRelation::new(map, String::from("R"));
+        assert_eq!(vec![String::from("NP\"-1")], rel.unwritable());
+    }
+
+    #[test]
+    fn test_relation_hierarchy() {
+        let hierarchy: RelationHierarchy<u8, u8> = "level:\n0 [0, 1, 2]\n9 *\n\
+                                                    level:\n1 [0, 1]\n2 [2]\n9 *".parse().unwrap();
+        assert_eq!(2, hierarchy.levels().len());
+        assert_eq!(0, hierarchy.coarsening(0).project(&1));
+        assert_eq!(0, hierarchy.coarsening(0).project(&2));
+        assert_eq!(9, hierarchy.coarsening(0).project(&9));
+
+        let relabellings = hierarchy.relabellings();
+        assert_eq!(1, relabellings[0].project(&0));
+        assert_eq!(0, relabellings[1].project(&relabellings[0].project(&2)));
+
+        let reparsed: RelationHierarchy<u8, u8> = hierarchy.to_string().parse().unwrap();
+        assert_eq!(hierarchy.levels(), reparsed.levels());
+
+        let single_level: RelationHierarchy<u8, u8> = "0 [0, 1]\n1 *".parse().unwrap();
+        assert_eq!(1, single_level.levels().len());
+
+        let illegal_inputs = vec![
+            "level:\n0 [0, 1]\n9 *\nlevel:\n1 [1, 2]\n9 *",
+            "level:\n0 [0, 1]\n9 *\nlevel:\n1 [0]\n9 *",
+            "0 [0]\n9 *\nlevel:\n0 [0]\n9 *",
+            "",
+        ];
+        for illegal_input in illegal_inputs {
+            assert!(illegal_input.parse::<RelationHierarchy<u8, u8>>().is_err());
+        }
+    }
+
     #[test]
     fn test_parse_set_legal_input() {
         let legal_inputs = vec![
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::Hash;
+use num_traits::One;
+
+use cfg::{CFG, CFGComposition, CFGRule, LetterT};
+use pmcfg::{PMCFG, PMCFGRule, VarT};
+
+/// Maps each rule of a coarse grammar to the rules of the fine grammar that it approximates.
+/// Grammar-level approximations (`split_components`, `prune_rules`, and `pmcfg::relabel::relabel`)
+/// return such a map alongside the coarse grammar; `restrict` uses it to prune the fine grammar
+/// to the rules that occur in coarse parses.
+pub type RuleMap<R2, R1> = HashMap<R2, Vec<R1>>;
+
+/// Returns the label of the `i`th component of the nonterminal `nt` with the given `fanout`:
+/// `nt` itself if the fan-out is one, and `nt{separator}{i + 1}` otherwise.
+fn component_label<N: Display>(nt: &N, i: usize, fanout: usize, separator: &str) -> String {
+    if fanout == 1 {
+        nt.to_string()
+    } else {
+        format!("{}{}{}", nt, separator, i + 1)
+    }
+}
+
+/// Approximates the `grammar` by a `CFG` whose nonterminals are the components of the
+/// nonterminals of the `grammar` (the fan-out-1 approximation): each rule
+/// `A → [u_1, …, u_k] (B_1, …, B_n)` yields the `k` rules `A_i → u_i`, where each variable
+/// `Var j l` is replaced by the nonterminal `(B_{j+1})_{l+1}`.
+/// Nonterminals of fan-out one keep their label; if a label `A_i` is already used by one of them,
+/// the components are labelled `A__i` instead (and so on).
+/// The rule `A_1 → u_1` keeps the weight of the original rule, the rules for the further
+/// components have weight one, such that the weight of a derivation is preserved.
+/// The `CFG` generates a superset of the language of the `grammar`.
+/// Fine rules that yield the same coarse rule are merged as in `pmcfg::relabel::relabel`.
+///
+/// ```
+/// use rustomata::approximation::grammar::split_components;
+/// use rustomata::cfg::CFGRule;
+/// use rustomata::pmcfg::PMCFG;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[Var 0 0, Var 0 1]] (A)\n\
+///                                            A → [[T a, Var 0 0], [T b, Var 0 1]] (A) # 0.5\n\
+///                                            A → [[], []] () # 0.5".parse().unwrap();
+/// let (cfg, rule_map) = split_components(&grammar);
+///
+/// let rule: CFGRule<String, String, f64> = "A_2 → [T b, Nt A_2]".parse().unwrap();
+/// assert!(cfg.rules.contains(&rule));
+/// assert_eq!(vec![grammar.rules[1].clone()], rule_map[&rule]);
+/// assert_eq!(5, cfg.rules.len());
+/// assert_eq!(1.0, cfg.rules.iter().find(|r| r == &&rule).unwrap().weight);
+/// ```
+pub fn split_components<N, T, W>(grammar: &PMCFG<N, T, W>)
+                                 -> (CFG<String, T, W>, RuleMap<CFGRule<String, T, W>, PMCFGRule<N, T, W>>)
+    where N: Clone + Display + Eq + Hash,
+          T: Clone + Eq + Hash,
+          W: Clone + One + PartialOrd,
+{
+    let mut fanouts = HashMap::new();
+    for rule in &grammar.rules {
+        fanouts.insert(&rule.head, rule.composition.composition.len());
+    }
+    let fanout = |nt: &N| *fanouts.get(nt).unwrap_or(&1);
+
+    let mut nonterminals: HashSet<&N> = grammar.initial.iter().collect();
+    for rule in &grammar.rules {
+        nonterminals.insert(&rule.head);
+        nonterminals.extend(rule.tail.iter());
+    }
+    let labels: HashSet<String> = nonterminals.iter()
+        .filter(|&&nt| fanout(nt) == 1)
+        .map(|nt| nt.to_string())
+        .collect();
+    let mut separator = "_".to_string();
+    while nonterminals.iter().any(|&nt| {
+        let k = fanout(nt);
+        k > 1 && (0..k).any(|i| labels.contains(&component_label(nt, i, k, &separator)))
+    }) {
+        separator.push('_');
+    }
+
+    let mut rules: Vec<CFGRule<String, T, W>> = Vec::new();
+    let mut fine_rules: Vec<Vec<PMCFGRule<N, T, W>>> = Vec::new();
+    let mut index = HashMap::new();
+
+    for rule in &grammar.rules {
+        for (i, component) in rule.composition.composition.iter().enumerate() {
+            let composition: Vec<_> = component
+                .iter()
+                .map(|symbol| match *symbol {
+                    VarT::Var(j, l) => {
+                        let successor = &rule.tail[j];
+                        LetterT::Label(component_label(successor, l, fanout(successor), &separator))
+                    },
+                    VarT::T(ref t) => LetterT::Value(t.clone()),
+                })
+                .collect();
+            let weight = if i == 0 { rule.weight.clone() } else { W::one() };
+            let coarse_rule = CFGRule {
+                head: component_label(&rule.head, i, fanout(&rule.head), &separator),
+                composition: CFGComposition::from(composition),
+                weight: weight.clone(),
+            };
+            let k = *index.entry(coarse_rule.clone()).or_insert(rules.len());
+
+            if k == rules.len() {
+                rules.push(coarse_rule);
+                fine_rules.push(Vec::new());
+            } else if rules[k].weight < weight {
+                rules[k].weight = weight;
+            }
+            if !fine_rules[k].contains(rule) {
+                fine_rules[k].push(rule.clone());
+            }
+        }
+    }
+
+    let mut initial = Vec::new();
+    for nt in &grammar.initial {
+        let label = component_label(nt, 0, fanout(nt), &separator);
+        if !initial.contains(&label) {
+            initial.push(label);
+        }
+    }
+
+    let rule_map = rules.iter().cloned().zip(fine_rules).collect();
+    (CFG { initial, rules }, rule_map)
+}
+
+/// Removes the rules of the `grammar` whose weight is less than the `threshold`.
+/// Returns the pruned grammar and the map from each remaining rule to itself.
+///
+/// ```
+/// use rustomata::approximation::grammar::prune_rules;
+/// use rustomata::pmcfg::PMCFG;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[T a]] () # 0.9\n\
+///                                            S → [[T b]] () # 0.1".parse().unwrap();
+/// let (pruned, _) = prune_rules(&grammar, 0.5);
+///
+/// assert_eq!(vec![grammar.rules[0].clone()], pruned.rules);
+/// ```
+pub fn prune_rules<N, T, W>(grammar: &PMCFG<N, T, W>, threshold: W)
+                            -> (PMCFG<N, T, W>, RuleMap<PMCFGRule<N, T, W>, PMCFGRule<N, T, W>>)
+    where N: Clone + Eq + Hash,
+          T: Clone + Eq + Hash,
+          W: Clone + PartialOrd,
+{
+    let rules: Vec<_> = grammar.rules.iter().filter(|r| r.weight >= threshold).cloned().collect();
+    let rule_map = rules.iter().map(|r| (r.clone(), vec![r.clone()])).collect();
+    (PMCFG { initial: grammar.initial.clone(), rules }, rule_map)
+}
+
+/// Restricts the fine `grammar` to the rules that are approximated by one of the `coarse_rules`
+/// according to the `rule_map`, e.g. to the rules of the derivations that were found with the
+/// coarse grammar.
+///
+/// ```
+/// use rustomata::approximation::grammar::{restrict, split_components};
+/// use rustomata::pmcfg::PMCFG;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[T a]] ()\n\
+///                                            S → [[T b]] ()".parse().unwrap();
+/// let (cfg, rule_map) = split_components(&grammar);
+/// let restricted = restrict(&grammar, &rule_map, &cfg.rules[1..]);
+///
+/// assert_eq!(vec![grammar.rules[1].clone()], restricted.rules);
+/// ```
+pub fn restrict<R2, N, T, W>(grammar: &PMCFG<N, T, W>,
+                             rule_map: &RuleMap<R2, PMCFGRule<N, T, W>>,
+                             coarse_rules: &[R2])
+                             -> PMCFG<N, T, W>
+    where R2: Eq + Hash,
+          N: Clone + Eq + Hash,
+          T: Clone + Eq + Hash,
+          W: Clone,
+{
+    let mut fine_rules = HashSet::new();
+    for coarse_rule in coarse_rules {
+        if let Some(rules) = rule_map.get(coarse_rule) {
+            fine_rules.extend(rules);
+        }
+    }
+
+    PMCFG {
+        initial: grammar.initial.clone(),
+        rules: grammar.rules.iter().filter(|r| fine_rules.contains(r)).cloned().collect(),
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::Hash;
+
+use approximation::equivalence_classes::EquivalenceRelation;
+use pmcfg::PMCFG;
+
+/// Label of the default class of induced relations; primes are appended until it differs from
+/// the labels of all other classes.
+pub const DEFAULT_CLASS: &'static str = "OTHER";
+
+/// Returns the fan-out of every nonterminal that occurs on the left-hand side of a rule.
+fn fanouts<N, T, W>(grammar: &PMCFG<N, T, W>) -> BTreeMap<N, usize>
+    where N: Clone + Ord,
+{
+    let mut fanouts = BTreeMap::new();
+    for rule in &grammar.rules {
+        fanouts.insert(rule.head.clone(), rule.composition.composition.len());
+    }
+    fanouts
+}
+
+/// Builds the `EquivalenceRelation` with the given classes and a fresh default class.
+fn relation_from_classes<N>(classes: HashMap<String, HashSet<N>>) -> EquivalenceRelation<N, String>
+    where N: Eq + Hash,
+{
+    let mut default = DEFAULT_CLASS.to_string();
+    while classes.contains_key(&default) {
+        default.push('\'');
+    }
+    EquivalenceRelation::new(classes, default)
+}
+
+/// Merges all nonterminals of the `grammar` with the same fan-out `k` into the class `Fk`.
+///
+/// ```
+/// use rustomata::approximation::induction::by_fanout;
+/// use rustomata::pmcfg::PMCFG;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[Var 0 0, Var 1 0, Var 0 1]] (A, B)\n\
+///                                            A → [[T a], [T c]] ()\n\
+///                                            B → [[T b]] ()".parse().unwrap();
+/// let relation = by_fanout(&grammar);
+///
+/// assert_eq!("F1", relation.project(&"S".to_string()));
+/// assert_eq!("F2", relation.project(&"A".to_string()));
+/// assert_eq!("F1", relation.project(&"B".to_string()));
+/// ```
+pub fn by_fanout<N, T, W>(grammar: &PMCFG<N, T, W>) -> EquivalenceRelation<N, String>
+    where N: Clone + Eq + Hash + Ord,
+{
+    let mut classes = HashMap::new();
+    for (nonterminal, fanout) in fanouts(grammar) {
+        classes
+            .entry(format!("F{}", fanout))
+            .or_insert_with(HashSet::new)
+            .insert(nonterminal);
+    }
+    relation_from_classes(classes)
+}
+
+/// Returns the part of `label` before the first of the `separators` that is neither its first
+/// nor its last character, e.g. `NP` for `NP-SBJ` and `-NONE-` for `-NONE-`.
+fn label_prefix<'a>(label: &'a str, separators: &[char]) -> &'a str {
+    let inner_separator = label
+        .char_indices()
+        .skip(1)
+        .find(|&(i, c)| separators.contains(&c) && i + c.len_utf8() < label.len());
+    match inner_separator {
+        Some((i, _)) => &label[..i],
+        None => label,
+    }
+}
+
+/// Merges all nonterminals of the `grammar` whose labels have the same prefix up to one of the
+/// `separators` (e.g. `NP-SBJ` and `NP-TMP` into `NP` for the separator `-`).
+/// The prefixes are the labels of the classes.
+///
+/// ```
+/// use rustomata::approximation::induction::by_label_prefix;
+/// use rustomata::pmcfg::PMCFG;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[Var 0 0, Var 1 0]] (\"NP-SBJ\", VP)\n\
+///                                            VP → [[T sleeps]] ()\n\
+///                                            \"NP-SBJ\" → [[T Kim]] ()".parse().unwrap();
+/// let relation = by_label_prefix(&grammar, &['-', '=']);
+///
+/// assert_eq!("NP", relation.project(&"NP-SBJ".to_string()));
+/// assert_eq!("VP", relation.project(&"VP".to_string()));
+/// ```
+pub fn by_label_prefix<T, W>(grammar: &PMCFG<String, T, W>, separators: &[char])
+                             -> EquivalenceRelation<String, String>
+{
+    let mut nonterminals: BTreeSet<&String> = grammar.initial.iter().collect();
+    for rule in &grammar.rules {
+        nonterminals.insert(&rule.head);
+        nonterminals.extend(&rule.tail);
+    }
+
+    let mut classes = HashMap::new();
+    for nonterminal in nonterminals {
+        classes
+            .entry(label_prefix(nonterminal, separators).to_string())
+            .or_insert_with(HashSet::new)
+            .insert(nonterminal.clone());
+    }
+    relation_from_classes(classes)
+}
+
+/// Returns, for every nonterminal on the left-hand side of a rule, how often it occurs in each
+/// context: as the left-hand side of a rule with the same composition and successors, or as the
+/// `i`th successor of a rule with the same left-hand side and composition.
+fn contexts<N, T, W>(grammar: &PMCFG<N, T, W>) -> BTreeMap<N, HashMap<String, f64>>
+    where N: Clone + Display + Ord,
+          T: Display,
+{
+    let mut contexts: BTreeMap<N, HashMap<String, f64>> = BTreeMap::new();
+    for nonterminal in fanouts(grammar).keys() {
+        contexts.insert(nonterminal.clone(), HashMap::new());
+    }
+
+    for rule in &grammar.rules {
+        let tail: Vec<String> = rule.tail.iter().map(|n| n.to_string()).collect();
+        let context = format!("→ {} ({})", rule.composition, tail.join(", "));
+        if let Some(counts) = contexts.get_mut(&rule.head) {
+            *counts.entry(context).or_insert(0.0) += 1.0;
+        }
+
+        for (i, successor) in rule.tail.iter().enumerate() {
+            let context = format!("{} → {} @{}", rule.head, rule.composition, i);
+            if let Some(counts) = contexts.get_mut(successor) {
+                *counts.entry(context).or_insert(0.0) += 1.0;
+            }
+        }
+    }
+
+    contexts
+}
+
+fn cosine(v1: &HashMap<String, f64>, v2: &HashMap<String, f64>) -> f64 {
+    let norm = |v: &HashMap<String, f64>| v.values().map(|x| x * x).sum::<f64>().sqrt();
+    let product: f64 = v1.iter().map(|(k, x)| x * v2.get(k).unwrap_or(&0.0)).sum();
+    if product == 0.0 {
+        0.0
+    } else {
+        product / (norm(v1) * norm(v2))
+    }
+}
+
+/// Clusters the nonterminals of the `grammar` into (at most) `number_of_classes` classes by the
+/// contexts they occur in (cf. `contexts`): starting with one class per nonterminal, the two
+/// classes of the same fan-out whose summed context counts have the greatest cosine similarity
+/// are merged until `number_of_classes` is reached or no two classes have the same fan-out.
+/// Each class is labelled by its least element.
+///
+/// Every merge compares all pairs of classes, i.e. the clustering takes cubic time in the number
+/// of nonterminals.
+///
+/// ```
+/// use rustomata::approximation::induction::by_distribution;
+/// use rustomata::pmcfg::PMCFG;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[Var 0 0, Var 1 0]] (A, B)\n\
+///                                            S → [[Var 0 0, Var 1 0]] (C, B)\n\
+///                                            A → [[T a]] ()\n\
+///                                            B → [[T b]] ()\n\
+///                                            C → [[T a]] ()".parse().unwrap();
+/// let relation = by_distribution(&grammar, 3);
+///
+/// assert_eq!("A", relation.project(&"C".to_string()));
+/// assert_eq!("B", relation.project(&"B".to_string()));
+/// ```
+pub fn by_distribution<N, T, W>(grammar: &PMCFG<N, T, W>, number_of_classes: usize)
+                                -> EquivalenceRelation<N, String>
+    where N: Clone + Display + Eq + Hash + Ord,
+          T: Display,
+{
+    let fanouts = fanouts(grammar);
+    let mut clusters: Vec<(Vec<N>, HashMap<String, f64>, usize)> = contexts(grammar)
+        .into_iter()
+        .map(|(nonterminal, counts)| {
+            let fanout = fanouts[&nonterminal];
+            (vec![nonterminal], counts, fanout)
+        })
+        .collect();
+
+    while clusters.len() > number_of_classes {
+        let mut best: Option<(usize, usize, f64)> = None;
+        for i in 0..clusters.len() {
+            for j in i + 1..clusters.len() {
+                if clusters[i].2 != clusters[j].2 {
+                    continue;
+                }
+                let similarity = cosine(&clusters[i].1, &clusters[j].1);
+                if best.map_or(true, |(_, _, s)| similarity > s) {
+                    best = Some((i, j, similarity));
+                }
+            }
+        }
+
+        match best {
+            Some((i, j, _)) => {
+                let (members, counts, _) = clusters.remove(j);
+                clusters[i].0.extend(members);
+                for (context, count) in counts {
+                    *clusters[i].1.entry(context).or_insert(0.0) += count;
+                }
+            },
+            None => break,
+        }
+    }
+
+    let mut classes = HashMap::new();
+    for (members, _, _) in clusters {
+        let label = members.iter().min().unwrap().to_string();
+        classes.insert(label, members.into_iter().collect());
+    }
+    relation_from_classes(classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_induction() {
+        let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+                                                   S → [[Var 0 0, Var 1 0, Var 0 1, Var 1 1]] (A, B)\n\
+                                                   A → [[T a, Var 0 0], [T c, Var 0 1]] (A)\n\
+                                                   A → [[], []] ()\n\
+                                                   B → [[T b, Var 0 0], [T d, Var 0 1]] (B)\n\
+                                                   B → [[], []] ()".parse().unwrap();
+
+        let relation = by_fanout(&grammar);
+        assert_eq!("F2", relation.project(&"A".to_string()));
+        assert_eq!("F2", relation.project(&"B".to_string()));
+        assert_eq!("F1", relation.project(&"S".to_string()));
+        assert_eq!(DEFAULT_CLASS, relation.project(&"C".to_string()));
+
+        let relation = by_distribution(&grammar, 2);
+        assert_eq!("A", relation.project(&"B".to_string()));
+        assert_eq!("S", relation.project(&"S".to_string()));
+
+        let relation = by_distribution(&grammar, 1);
+        assert_eq!("S", relation.project(&"S".to_string()));
+
+        let relation = by_label_prefix(&grammar, &['-']);
+        assert_eq!(relation, relation.to_string().parse().unwrap());
+
+        assert_eq!("NP", label_prefix("NP-SBJ-1", &['-', '=']));
+        assert_eq!("NP", label_prefix("NP=2", &['-', '=']));
+        assert_eq!("-NONE-", label_prefix("-NONE-", &['-', '=']));
+        assert_eq!("NP-", label_prefix("NP-", &['-', '=']));
+    }
+}
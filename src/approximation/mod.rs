@@ -1,12 +1,16 @@
 use num_traits::One;
 use std::collections::{BinaryHeap, BTreeMap};
-use std::ops::MulAssign;
+use std::ops::{Mul, MulAssign};
+use std::time::{Duration, Instant};
 
-use recognisable::{Instruction, Transition};
-use recognisable::automaton::Automaton;
+use recognisable::{Configuration, Instruction, Item, Recognisable, Transition};
+use recognisable::automaton::{Automaton, apply_to_configurations, recognise};
 use util::push_down::Pushdown;
 
+pub mod compose;
 pub mod equivalence_classes;
+pub mod grammar;
+pub mod induction;
 pub mod relabel;
 pub mod tts;
 
@@ -83,6 +87,61 @@ impl<Strategy, T, W> ApproximationInstance<Strategy, T, W>
         t2
     }
 
+    /// Returns, for each coarse transition, the number of fine transitions that it approximates.
+    pub fn fine_transition_counts(&self) -> Vec<usize> {
+        self.reverse_transition_map.values().map(Vec::len).collect()
+    }
+
+    /// Compares the `fine` automaton with the `coarse` one that was obtained by this instance:
+    /// counts the transitions of both, searches the first `n` coarse parses of each sentence of
+    /// the `corpus`, and checks which of them are invalid in the `fine` automaton.
+    /// The time needed to search the first `n` parses of each sentence in the `fine` automaton is
+    /// measured as well.
+    pub fn report<A, R>(&self, fine: &A, coarse: &R, corpus: &[Vec<T>], n: usize) -> ApproximationReport
+        where A: Automaton<T, W, I=Strategy::I1>,
+              <Strategy::I1 as Instruction>::Storage: Clone + Eq,
+              A::IInt: Ord,
+              <A::IInt as Instruction>::Storage: Clone + Ord,
+              A::TInt: Clone + Ord,
+              R: Recognisable<T, W, Parse=Item<<Strategy::I2 as Instruction>::Storage, Strategy::I2, T, W>>,
+              W: Copy + Mul<Output=W>,
+    {
+        let counts = self.fine_transition_counts();
+        let mut report = ApproximationReport {
+            coarse_transitions: counts.len(),
+            fine_transitions: counts.iter().sum(),
+            max_fine_transitions: counts.iter().cloned().max().unwrap_or(0),
+            coarse_parses: 0,
+            invalid_parses: 0,
+            duration: Duration::new(0, 0),
+            fine_duration: Duration::new(0, 0),
+        };
+
+        for sentence in corpus {
+            let start = Instant::now();
+            let coarse_parses: Vec<_> = coarse.recognise(sentence.clone()).take(n).collect();
+            report.duration += start.elapsed();
+
+            let start = Instant::now();
+            let _ = recognise(fine, sentence.clone()).take(n).count();
+            report.fine_duration += start.elapsed();
+
+            let word: Option<Vec<_>> = sentence.iter().map(|t| fine.terminal_to_int(t)).collect();
+            for (_, coarse_run) in coarse_parses {
+                report.coarse_parses += 1;
+                let valid = match word {
+                    Some(ref word) => self.unapproximate_run_lazily(coarse_run, fine, word.clone()).next().is_some(),
+                    None => false,
+                };
+                if !valid {
+                    report.invalid_parses += 1;
+                }
+            }
+        }
+
+        report
+    }
+
     pub fn unapproximate_transition(&self, t2: &Transition<Strategy::I2, T, W>)
                                     -> Vec<Transition<Strategy::I1, T, W>>
     {
@@ -111,4 +170,171 @@ impl<Strategy, T, W> ApproximationInstance<Strategy, T, W>
         run2.iter().map(|t2| self.unapproximate_transition(&t2)).fold(initial_heap, f)
     }
 
+    /// Like `unapproximate_run` followed by `Automaton::check_run_int` with the fine
+    /// `automaton` and the `word` (in its internal representation), but the valid fine runs are
+    /// enumerated lazily in the order of descending weight, and a prefix of a fine run is
+    /// discarded as soon as one of its transitions cannot be applied.
+    /// The fine transitions are translated to the internal representation of the `automaton`
+    /// once, and so are the returned runs (cf. `Automaton::item_map`).
+    pub fn unapproximate_run_lazily<A>(&self,
+                                       run2: Pushdown<Transition<Strategy::I2, T, W>>,
+                                       automaton: &A,
+                                       word: Vec<A::TInt>)
+                                       -> FineRuns<A::IInt, A::TInt, W>
+        where A: Automaton<T, W, I=Strategy::I1>,
+              A::IInt: Ord,
+              <A::IInt as Instruction>::Storage: Clone + Ord,
+              A::TInt: Clone + Ord,
+              W: Copy + Mul<Output=W>,
+    {
+        let candidates: Vec<Vec<_>> = run2
+            .iter()
+            .map(|t2| {
+                self.unapproximate_transition(&t2)
+                    .iter()
+                    .filter_map(|t1| automaton.transition_to_int(t1))
+                    .collect()
+            })
+            .collect();
+        let initial = Configuration {
+            word,
+            storage: automaton.initial_int(),
+            weight: W::one(),
+        };
+        FineRuns::new(candidates, initial, A::is_terminal)
+    }
+
+}
+
+/// Statistics of an approximation on a corpus (cf. `ApproximationInstance::report`).
+#[derive(Clone, Debug)]
+pub struct ApproximationReport {
+    /// the number of coarse transitions
+    pub coarse_transitions: usize,
+    /// the number of fine transitions
+    pub fine_transitions: usize,
+    /// the greatest number of fine transitions that are approximated by one coarse transition
+    pub max_fine_transitions: usize,
+    /// the number of coarse parses that were found
+    pub coarse_parses: usize,
+    /// the number of coarse parses without a valid fine run
+    pub invalid_parses: usize,
+    /// the time needed to find the coarse parses
+    pub duration: Duration,
+    /// the time needed to find as many fine parses directly, for reference
+    pub fine_duration: Duration,
+}
+
+/// Iterator over the valid fine runs of a coarse run in the order of descending weight
+/// (cf. `ApproximationInstance::unapproximate_run_lazily`).
+///
+/// The prefixes of fine runs are explored best-first and stepped like in
+/// `Automaton::check_run_int`; the priority of a prefix is its weight times the greatest weight
+/// of the remaining fine transitions, which is an upper bound of the weight of each of its
+/// completions.
+pub struct FineRuns<I, T, W>
+    where I: Instruction,
+{
+    candidates: Vec<Vec<Transition<I, T, W>>>,
+    bounds: Vec<W>,
+    is_terminal: fn(&Configuration<I::Storage, T, W>) -> bool,
+    /// prefixes of fine runs with their priority, their length, and the configurations they reach
+    agenda: BinaryHeap<(W, usize, Vec<Configuration<I::Storage, T, W>>, Pushdown<Transition<I, T, W>>)>,
+    /// the terminal configurations of a fine run that are not yet returned
+    buffer: Vec<Item<I::Storage, I, T, W>>,
+    failed_transition: Option<usize>,
+}
+
+impl<I, T, W> FineRuns<I, T, W>
+    where I: Clone + Instruction + Ord,
+          I::Storage: Clone + Ord,
+          T: Clone + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
+{
+    /// Creates the `FineRuns` that apply one of the `candidates[i]` as their `i`th transition,
+    /// starting with the configuration `initial`, and end in a configuration that satisfies
+    /// `is_terminal`.
+    pub fn new(candidates: Vec<Vec<Transition<I, T, W>>>,
+               initial: Configuration<I::Storage, T, W>,
+               is_terminal: fn(&Configuration<I::Storage, T, W>) -> bool)
+               -> Self
+    {
+        let mut bounds = vec![W::one()];
+        let mut agenda = BinaryHeap::new();
+
+        // if one of the coarse transitions approximates no fine transition, there is no fine run
+        if candidates.iter().all(|ts| !ts.is_empty()) {
+            for ts in candidates.iter().rev() {
+                let mut bound = ts.iter().map(|t| t.weight).max().unwrap();
+                bound *= *bounds.last().unwrap();
+                bounds.push(bound);
+            }
+            bounds.reverse();
+            agenda.push((bounds[0] * initial.weight, 0, vec![initial], Pushdown::new()));
+        }
+
+        FineRuns { candidates, bounds, is_terminal, agenda, buffer: Vec::new(), failed_transition: None }
+    }
+
+    /// Returns the index of the transition at which the longest prefixes of fine runs that were
+    /// explored so far got stuck, like the error of `Automaton::check_run_int`, i.e. the number
+    /// of transitions if a fine run could be applied, but did not end in a terminal
+    /// configuration.
+    /// Once the iterator is exhausted without returning a fine run, this tells how far the coarse
+    /// run could be followed in the fine automaton.
+    pub fn failed_transition(&self) -> Option<usize> {
+        self.failed_transition
+    }
+
+    fn fail(&mut self, i: usize) {
+        if self.failed_transition.map_or(true, |j| i > j) {
+            self.failed_transition = Some(i);
+        }
+    }
+}
+
+impl<I, T, W> Iterator for FineRuns<I, T, W>
+    where I: Clone + Instruction + Ord,
+          I::Storage: Clone + Ord,
+          T: Clone + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
+{
+    type Item = Item<I::Storage, I, T, W>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.buffer.pop() {
+                return Some(item);
+            }
+
+            let (_, i, configurations, run) = self.agenda.pop()?;
+            if i == self.candidates.len() {
+                let is_terminal = self.is_terminal;
+                for configuration in configurations {
+                    if is_terminal(&configuration) {
+                        self.buffer.push((configuration, run.clone()));
+                    }
+                }
+                if self.buffer.is_empty() {
+                    self.fail(i);
+                }
+                continue;
+            }
+
+            let mut stuck = false;
+            for t1 in &self.candidates[i] {
+                let configurations1 = apply_to_configurations(t1, &configurations);
+                if configurations1.is_empty() {
+                    stuck = true;
+                    continue;
+                }
+                // all configurations that are reached by a run have the same weight
+                let priority = configurations1[0].weight * self.bounds[i + 1];
+                self.agenda.push((priority, i + 1, configurations1, run.clone().push(t1.clone())));
+            }
+            if stuck {
+                self.fail(i);
+            }
+        }
+    }
 }
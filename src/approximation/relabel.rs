@@ -1,9 +1,10 @@
 use num_traits::Zero;
 use std::hash::Hash;
-use std::ops::{AddAssign, MulAssign};
+use std::ops::{AddAssign, Mul, MulAssign};
 
 use approximation::*;
 use push_down_automaton::*;
+use tree_stack_automaton::*;
 
 /// `ApproximationStrategy` that uses the `Relabel` trait to relabel internal values via an `EquivalenceClass`
 pub struct RlbElement<'a, A1, A2>
@@ -50,6 +51,45 @@ impl<'a, A1, A2, T, W> ApproximationStrategy<T, W> for RlbElement<'a, A1, A2>
     }
 }
 
+/// `ApproximationStrategy` that relabels the values of a `TreeStackAutomaton` like `RlbElement`,
+/// such that the approximation is still a `TreeStackAutomaton`
+pub struct TreeStackRlbElement<'a, A1, A2>
+    where A1: 'a,
+          A2: 'a,
+{
+    pub mapping: &'a Fn(&A1) -> A2
+}
+
+impl<'a, A1, A2> TreeStackRlbElement<'a, A1, A2> {
+    pub fn new(mapping: &'a Fn(&A1) -> A2) -> Self {
+        TreeStackRlbElement {
+            mapping,
+        }
+    }
+}
+
+impl<'a, A1, A2, T, W> ApproximationStrategy<T, W> for TreeStackRlbElement<'a, A1, A2>
+    where A1: Clone + Hash + Ord,
+          A2: Clone + Hash + Ord,
+          T: Clone + Eq + Hash + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
+{
+    type I1 = TreeStackInstruction<A1>;
+    type I2 = TreeStackInstruction<A2>;
+    type A1 = TreeStackAutomaton<A1, T, W>;
+    type A2 = TreeStackAutomaton<A2, T, W>;
+
+    fn approximate_storage(&self, ts: TreeStack<A1>) -> TreeStack<A2> {
+        ts.map(&mut |a| (self.mapping)(a))
+    }
+
+    fn approximate_instruction(&self, instr: &TreeStackInstruction<A1>)
+                               -> TreeStackInstruction<A2>
+    {
+        instr.map(self.mapping)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,4 +137,25 @@ mod tests {
             <RlbElement<_, _> as ApproximationStrategy<char, u8>>::approximate_instruction(&rlb, &instruction)
         );
     }
+
+    #[test]
+    fn test_approximate_tree_stack() {
+        let rel = EquivalenceRelation::from_str("0 [0, 1]\n1 [2, 3]\n2 *").unwrap();
+        let mapping = |x: &u8| rel.project(x);
+        let rlb = TreeStackRlbElement::new(&mapping);
+
+        let instruction = TreeStackInstruction::Up { n: 0, current_val: 1, old_val: 4, new_val: 3 };
+        let control_instruction = TreeStackInstruction::Up { n: 0, current_val: 0, old_val: 2, new_val: 1 };
+        assert_eq!(
+            control_instruction,
+            <TreeStackRlbElement<_, _> as ApproximationStrategy<char, u8>>::approximate_instruction(&rlb, &instruction)
+        );
+
+        let tree_stack = TreeStack::new(1).push(0, 3).unwrap();
+        let control_tree_stack = TreeStack::new(0).push(0, 1).unwrap();
+        assert_eq!(
+            control_tree_stack,
+            <TreeStackRlbElement<_, _> as ApproximationStrategy<char, u8>>::approximate_storage(&rlb, tree_stack)
+        );
+    }
 }
@@ -1,17 +1,22 @@
 use clap::{Arg, ArgMatches, App, SubCommand};
 use log_domain::LogDomain;
-use rustomata::pmcfg::PMCFG;
+use rustomata::pmcfg::{PMCFG, PMCFGRule};
 use rustomata::cfg::CFG;
 use rustomata::recognisable::Recognisable;
-use rustomata::tree_stack_automaton::TreeStackAutomaton;
+use rustomata::tree_stack_automaton::{PosState, TreeStackAutomaton};
 use rustomata::push_down_automaton::{PushDownAutomaton, PushState};
-use rustomata::approximation::ApproximationStrategy;
-use rustomata::approximation::relabel::RlbElement;
+use rustomata::approximation::{ApproximationReport, ApproximationStrategy};
+use rustomata::approximation::relabel::{RlbElement, TreeStackRlbElement};
 use rustomata::approximation::tts::TTSElement;
 use rustomata::approximation::equivalence_classes::EquivalenceRelation;
+use rustomata::approximation::induction::{by_distribution, by_fanout, by_label_prefix};
+use rustomata::approximation::grammar::{prune_rules, split_components};
+use rustomata::pmcfg::relabel::relabel;
 
+use std::collections::BTreeSet;
 use std::io::{self, Read};
 use std::fs::File;
+use std::process;
 
 pub fn get_sub_command() -> App<'static, 'static> {
     SubCommand::with_name("approximation")
@@ -33,6 +38,12 @@ pub fn get_sub_command() -> App<'static, 'static> {
                                 .help("cfg-grammar file to use")
                                 .index(2)
                                 .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("mcfg")
+                                .help("reads an mcfg-grammar and relabels its tree-stack automaton")
+                                .long("mcfg")
+                                .required(false),
                         ),
                 )
                 .subcommand(
@@ -48,9 +59,116 @@ pub fn get_sub_command() -> App<'static, 'static> {
                                 .help("cfg-grammar file to use")
                                 .index(2)
                                 .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("mcfg")
+                                .help("reads an mcfg-grammar and relabels its tree-stack automaton")
+                                .long("mcfg")
+                                .required(false),
                         ),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("classes")
+                .about("induces equivalence classes of the nonterminals of a grammar and prints them as a classes file")
+                .arg(
+                    Arg::with_name("grammar")
+                        .help("cfg-grammar file to use")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("mcfg")
+                        .help("reads an mcfg-grammar instead of a cfg-grammar")
+                        .long("mcfg")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("method")
+                        .help("merges nonterminals with the same fan-out, with the same label prefix, or with similar contexts in the rules")
+                        .long("method")
+                        .value_name("method")
+                        .possible_values(&["fanout", "prefix", "distribution"])
+                        .default_value("prefix")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("separators")
+                        .help("characters that end the prefix of a label")
+                        .long("separators")
+                        .value_name("separators")
+                        .default_value("-=^|")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("number-of-classes")
+                        .help("number of classes to which the nonterminals are clustered with the method distribution")
+                        .short("n")
+                        .long("number")
+                        .value_name("number-of-classes")
+                        .default_value("10")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("grammar")
+                .about("approximates an mcfg-grammar by a coarser grammar and prints it")
+                .arg(
+                    Arg::with_name("grammar")
+                        .help("mcfg-grammar file to use")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("method")
+                        .help("splits the nonterminals into their components (yielding a cfg-grammar), relabels them with a classes file, or removes light rules")
+                        .long("method")
+                        .value_name("method")
+                        .possible_values(&["split", "relabel", "prune"])
+                        .default_value("split")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("classes")
+                        .help("classes file to use with the method relabel")
+                        .long("classes")
+                        .value_name("classes")
+                        .required_if("method", "relabel"),
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .help("least weight of the rules kept by the method prune")
+                        .long("threshold")
+                        .value_name("threshold")
+                        .required_if("method", "prune"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("prints statistics of the tts approximation (and of a subsequent relabelling) of an mcfg-grammar for the corpus from stdin")
+                .arg(
+                    Arg::with_name("grammar")
+                        .help("mcfg-grammar file to use")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("classes")
+                        .help("classes file with which the tts approximation is relabelled")
+                        .long("classes")
+                        .value_name("classes")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("number-of-parses")
+                        .help("number of coarse parses that are checked for each sentence")
+                        .short("n")
+                        .long("number")
+                        .value_name("number-of-parses")
+                        .default_value("10")
+                        .required(false),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("topk")
                 .about("maps pushdown to its topmost k elements")
@@ -107,64 +225,173 @@ pub fn get_sub_command() -> App<'static, 'static> {
         )
 }
 
+/// Prints the `report` of an approximation level that was computed for the first `n` coarse
+/// parses of each sentence.
+fn print_report(name: &str, report: &ApproximationReport, n: usize) {
+    println!("{}: {} coarse transitions for {} fine transitions (at most {}, on average {:.2} per coarse transition)",
+             name,
+             report.coarse_transitions,
+             report.fine_transitions,
+             report.max_fine_transitions,
+             report.fine_transitions as f64 / report.coarse_transitions.max(1) as f64);
+    println!("{}: {} of {} coarse parses are invalid in the fine automaton",
+             name, report.invalid_parses, report.coarse_parses);
+    println!("{}: {}.{:03}s for the first {} coarse parses of each sentence (fine: {}.{:03}s)",
+             name, report.duration.as_secs(), report.duration.subsec_nanos() / 1_000_000, n,
+             report.fine_duration.as_secs(), report.fine_duration.subsec_nanos() / 1_000_000);
+}
+
 pub fn handle_sub_matches(r_matches: &ArgMatches) {
     match r_matches.subcommand() {
         ("relabel", Some(relabel_matches)) => {
-            match relabel_matches.subcommand() {
-                ("parse", Some(parse_matches)) => {
-                    let grammar_file_name = parse_matches.value_of("grammar").unwrap();
-                    let mut grammar_file = File::open(grammar_file_name).unwrap();
-                    let mut grammar_string = String::new();
-                    let _ = grammar_file.read_to_string(&mut grammar_string);
-                    let g: CFG<String, String, LogDomain<f64>> =
-                        grammar_string.parse().unwrap();
+            let (subcommand, parse_matches) = match relabel_matches.subcommand() {
+                (subcommand, Some(parse_matches)) => (subcommand, parse_matches),
+                _ => return,
+            };
 
-                    let a = PushDownAutomaton::from(g);
+            let grammar_file_name = parse_matches.value_of("grammar").unwrap();
+            let mut grammar_file = File::open(grammar_file_name).unwrap();
+            let mut grammar_string = String::new();
+            let _ = grammar_file.read_to_string(&mut grammar_string);
 
-                    let classes_file_name = parse_matches.value_of("classes").unwrap();
-                    let mut classes_file = File::open(classes_file_name).unwrap();
-                    let mut classes_string = String::new();
-                    let _ = classes_file.read_to_string(&mut classes_string);
-                    let e: EquivalenceRelation<String, String> = classes_string.parse().unwrap();
+            let e = read_classes(parse_matches.value_of("classes").unwrap());
 
-                    let f = |ps: &PushState<_, _>| ps.map(|nt| e.project(nt));
-                    let rlb = RlbElement::new(&f);
+            let mut corpus = String::new();
+            if subcommand == "parse" {
+                let _ = io::stdin().read_to_string(&mut corpus);
+            }
 
-                    let (b, _) = rlb.approximate_automaton(&a);
+            if parse_matches.is_present("mcfg") {
+                let g: PMCFG<String, String, LogDomain<f64>> =
+                    grammar_string.parse().unwrap();
 
-                    let mut corpus = String::new();
-                    let _ = io::stdin().read_to_string(&mut corpus);
+                warn_unclassified(&g, &e);
+                let a = TreeStackAutomaton::from(g);
 
-                    for sentence in corpus.lines() {
-                        println!("{:?}: {}",
-                                 b.recognise(sentence.split_whitespace().map(|x| x.to_string()).collect()).next(),
-                                 sentence);
-                    }
-                }
-                ("automaton", Some(parse_matches)) => {
-                    let grammar_file_name = parse_matches.value_of("grammar").unwrap();
-                    let mut grammar_file = File::open(grammar_file_name).unwrap();
-                    let mut grammar_string = String::new();
-                    let _ = grammar_file.read_to_string(&mut grammar_string);
-                    let g: CFG<String, String, LogDomain<f64>> =
-                        grammar_string.parse().unwrap();
+                let f = |ps: &PosState<PMCFGRule<_, _, _>>| ps.map(|r| e.project(&r.head));
+                let rlb = TreeStackRlbElement::new(&f);
 
-                    let a = PushDownAutomaton::from(g);
+                let (b, _) = rlb.approximate_automaton(&a);
 
-                    let classes_file_name = parse_matches.value_of("classes").unwrap();
-                    let mut classes_file = File::open(classes_file_name).unwrap();
-                    let mut classes_string = String::new();
-                    let _ = classes_file.read_to_string(&mut classes_string);
-                    let e: EquivalenceRelation<String, String> = classes_string.parse().unwrap();
+                match subcommand {
+                    "parse" => {
+                        for sentence in corpus.lines() {
+                            println!("{:?}: {}",
+                                     b.recognise(sentence.split_whitespace().map(|x| x.to_string()).collect()).next(),
+                                     sentence);
+                        }
+                    },
+                    _ => println!("{}", b),
+                }
+            } else {
+                let g: CFG<String, String, LogDomain<f64>> =
+                    grammar_string.parse().unwrap();
 
-                    let f = |ps: &PushState<_, _>| ps.map(|nt| e.project(nt));
-                    let rlb = RlbElement::new(&f);
+                warn_unclassified(&PMCFG::from(g.clone()), &e);
+                let a = PushDownAutomaton::from(g);
 
-                    let (b, _) = rlb.approximate_automaton(&a);
+                let f = |ps: &PushState<_, _>| ps.map(|nt| e.project(nt));
+                let rlb = RlbElement::new(&f);
 
-                    println!("{}", b);
+                let (b, _) = rlb.approximate_automaton(&a);
+
+                match subcommand {
+                    "parse" => {
+                        for sentence in corpus.lines() {
+                            println!("{:?}: {}",
+                                     b.recognise(sentence.split_whitespace().map(|x| x.to_string()).collect()).next(),
+                                     sentence);
+                        }
+                    },
+                    _ => println!("{}", b),
                 }
-                _ => (),
+            }
+        }
+        ("classes", Some(classes_matches)) => {
+            let grammar_file_name = classes_matches.value_of("grammar").unwrap();
+            let mut grammar_file = File::open(grammar_file_name).unwrap();
+            let mut grammar_string = String::new();
+            let _ = grammar_file.read_to_string(&mut grammar_string);
+            let g: PMCFG<String, String, LogDomain<f64>> = if classes_matches.is_present("mcfg") {
+                grammar_string.parse().unwrap()
+            } else {
+                let g: CFG<String, String, LogDomain<f64>> = grammar_string.parse().unwrap();
+                PMCFG::from(g)
+            };
+
+            let e = match classes_matches.value_of("method").unwrap() {
+                "fanout" => by_fanout(&g),
+                "distribution" => {
+                    let n = classes_matches.value_of("number-of-classes").unwrap().parse().unwrap();
+                    by_distribution(&g, n)
+                },
+                _ => {
+                    let separators: Vec<char> = classes_matches.value_of("separators").unwrap().chars().collect();
+                    by_label_prefix(&g, &separators)
+                },
+            };
+
+            let unwritable = e.unwritable();
+            if !unwritable.is_empty() {
+                eprintln!("Error: the labels {} can not be written as classes", unwritable.join(", "));
+                process::exit(1);
+            }
+            println!("{}", e);
+        }
+        ("grammar", Some(grammar_matches)) => {
+            let grammar_file_name = grammar_matches.value_of("grammar").unwrap();
+            let mut grammar_file = File::open(grammar_file_name).unwrap();
+            let mut grammar_string = String::new();
+            let _ = grammar_file.read_to_string(&mut grammar_string);
+            let g: PMCFG<String, String, LogDomain<f64>> = grammar_string.parse().unwrap();
+
+            match grammar_matches.value_of("method").unwrap() {
+                "relabel" => {
+                    let e = read_classes(grammar_matches.value_of("classes").unwrap());
+
+                    warn_unclassified(&g, &e);
+                    let (coarse_grammar, _) = relabel(&g, &e);
+                    println!("{}", coarse_grammar);
+                },
+                "prune" => {
+                    let threshold: LogDomain<f64> =
+                        grammar_matches.value_of("threshold").unwrap().parse().unwrap();
+
+                    let (coarse_grammar, _) = prune_rules(&g, threshold);
+                    println!("{}", coarse_grammar);
+                },
+                _ => {
+                    let (coarse_grammar, _) = split_components(&g);
+                    println!("{}", coarse_grammar);
+                },
+            }
+        }
+        ("report", Some(report_matches)) => {
+            let grammar_file_name = report_matches.value_of("grammar").unwrap();
+            let mut grammar_file = File::open(grammar_file_name).unwrap();
+            let mut grammar_string = String::new();
+            let _ = grammar_file.read_to_string(&mut grammar_string);
+            let g: PMCFG<String, String, LogDomain<f64>> = grammar_string.parse().unwrap();
+            let n: usize = report_matches.value_of("number-of-parses").unwrap().parse().unwrap();
+
+            let mut corpus_string = String::new();
+            let _ = io::stdin().read_to_string(&mut corpus_string);
+            let corpus: Vec<Vec<String>> = corpus_string
+                .lines()
+                .map(|s| s.split_whitespace().map(|x| x.to_string()).collect())
+                .collect();
+
+            let a = TreeStackAutomaton::from(g.clone());
+            let (b, tts_instance) = TTSElement::new().approximate_automaton(&a);
+            print_report("tts", &tts_instance.report(&a, &b, &corpus, n), n);
+
+            if let Some(classes_file_name) = report_matches.value_of("classes") {
+                let e = read_classes(classes_file_name);
+                warn_unclassified(&g, &e);
+
+                let f = |ps: &PosState<PMCFGRule<_, _, _>>| ps.map(|r| r.map_nonterminals(|nt| e.project(nt)));
+                let (c, rlb_instance) = RlbElement::new(&f).approximate_automaton(&b);
+                print_report("rlb", &rlb_instance.report(&b, &c, &corpus, n), n);
             }
         }
         ("tts", Some(tts_matches)) => {
@@ -213,3 +440,31 @@ pub fn handle_sub_matches(r_matches: &ArgMatches) {
     }
 }
 
+/// Reads the classes file, or prints the error and exits if it is malformed.
+fn read_classes(classes_file_name: &str) -> EquivalenceRelation<String, String> {
+    let mut classes_file = File::open(classes_file_name).unwrap();
+    let mut classes_string = String::new();
+    let _ = classes_file.read_to_string(&mut classes_string);
+    match classes_string.parse() {
+        Ok(e) => e,
+        Err(error) => {
+            eprintln!("Error in the classes file {}: {}", classes_file_name, error);
+            process::exit(1);
+        },
+    }
+}
+
+/// Prints a warning for each nonterminal of the `grammar` that falls through to the default
+/// class of the `relation`.
+fn warn_unclassified(grammar: &PMCFG<String, String, LogDomain<f64>>, relation: &EquivalenceRelation<String, String>) {
+    let mut nonterminals: BTreeSet<&String> = grammar.initial.iter().collect();
+    for rule in &grammar.rules {
+        nonterminals.insert(&rule.head);
+        nonterminals.extend(&rule.tail);
+    }
+
+    for nonterminal in relation.unclassified(nonterminals) {
+        eprintln!("Warning: the nonterminal {} falls through to the default class {}",
+                  nonterminal, relation.default_class());
+    }
+}
@@ -1,11 +1,22 @@
 use clap::{Arg, ArgMatches, App, SubCommand};
 use log_domain::LogDomain;
-use rustomata::cfg::CFG;
-use rustomata::recognisable::Recognisable;
-use rustomata::push_down_automaton::PushDownAutomaton;
+use rustomata::cfg::{CFG, replace_terminals, smooth_lexical_rules, split_lexicon, to_bracket};
+use rustomata::cfg::extraction::{ExtractionOptions, extract_cfg};
+use rustomata::recognisable::{GaveUp, Item, SearchLimits, SearchOptions, SearchStatistics};
+use rustomata::recognisable::automaton::{Automaton, BeamMode, recognise_beam_mode_bounded, recognise_bounded};
+use rustomata::recognisable::batch::parse_batch;
+use rustomata::recognisable::lattice::{Lattice, recognise_lattice_beam_mode_bounded, recognise_lattice_bounded};
+use rustomata::recognisable::lexicon::{Lexicon, tagged_to_lattice, tagged_words};
+use rustomata::push_down_automaton::{PushDown, PushDownAutomaton, PushDownInstruction, PushState, to_derivation};
+use rustomata::util::tree::read_bracket_corpus;
+use rustomata::util::unknown_words::{LexicalSmoothing, replace_unknown_word, replace_unknown_words, validate_factor};
 
+use std::cell::RefCell;
 use std::io::{self, Read};
 use std::fs::File;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn get_sub_command() -> App<'static, 'static> {
     SubCommand::with_name("cfg")
@@ -29,6 +40,72 @@ pub fn get_sub_command() -> App<'static, 'static> {
                          .short("b")
                          .long("beam")
                          .value_name("beam-width")
+                         .required(false))
+                    .arg(Arg::with_name("beam-mode")
+                         .help("pruning policy of the beam: the whole agenda (default), items with the same remaining word length, or items with the same storage key share a beam")
+                         .long("beam-mode")
+                         .value_name("beam-mode")
+                         .possible_values(&["width", "position", "key"])
+                         .requires("beam-width")
+                         .required(false))
+                    .arg(Arg::with_name("beam-threshold")
+                         .help("drops items whose weight is less than the given factor (between 0 and 1) times the weight of the best item with the same remaining word length so far")
+                         .long("beam-threshold")
+                         .value_name("beam-threshold")
+                         .validator(validate_factor)
+                         .conflicts_with_all(&["beam-width", "beam-mode"])
+                         .required(false))
+                    .arg(Arg::with_name("max-items")
+                         .help("maximum number of items that are taken from the agenda for each sentence")
+                         .long("max-items")
+                         .value_name("max-items")
+                         .required(false))
+                    .arg(Arg::with_name("timeout")
+                         .help("maximum time (in milliseconds) that is spent searching for each sentence")
+                         .long("timeout")
+                         .value_name("timeout")
+                         .required(false))
+                    .arg(Arg::with_name("threads")
+                         .help("number of threads that parse sentences in parallel")
+                         .long("threads")
+                         .value_name("threads")
+                         .default_value("1")
+                         .required(false))
+                    .arg(Arg::with_name("smoothing")
+                         .help("handles unknown words: moves the given share of the weight of each lexical rule to rules for word signatures and UNK, and replaces unknown tokens by their signature or UNK")
+                         .long("smoothing")
+                         .value_name("smoothing")
+                         .validator(validate_factor)
+                         .required(false))
+                    .arg(Arg::with_name("output")
+                         .help("representation of the parses: the derivation of the grammar, a tree in bracket notation, or the final configuration of the automaton")
+                         .long("output")
+                         .value_name("output")
+                         .possible_values(&["derivation", "bracket", "configuration"])
+                         .default_value("configuration")
+                         .required(false))
+                    .arg(Arg::with_name("stats")
+                         .help("prints statistics of the search for each sentence to stderr")
+                         .long("stats")
+                         .required(false))
+                    .arg(Arg::with_name("lattice")
+                         .help("reads word lattices (separated by empty lines) instead of sentences")
+                         .long("lattice")
+                         .conflicts_with_all(&["tagged", "lexicon", "split-lexicon"])
+                         .required(false))
+                    .arg(Arg::with_name("tagged")
+                         .help("reads sentences of tokens of the form word/TAG and parses their tags")
+                         .long("tagged")
+                         .conflicts_with_all(&["lexicon", "split-lexicon"])
+                         .required(false))
+                    .arg(Arg::with_name("lexicon")
+                         .help("lexicon file that maps words to the preterminals of the grammar")
+                         .long("lexicon")
+                         .value_name("lexicon")
+                         .required(false))
+                    .arg(Arg::with_name("split-lexicon")
+                         .help("moves the lexical rules of the grammar to a lexicon that is used for parsing")
+                         .long("split-lexicon")
                          .required(false)))
         .subcommand(SubCommand::with_name("automaton")
                     .author("Max Korn <max.korn@tu-dresden.de>")
@@ -37,6 +114,24 @@ pub fn get_sub_command() -> App<'static, 'static> {
                          .help("grammar file to use")
                          .index(1)
                          .required(true)))
+        .subcommand(SubCommand::with_name("extract")
+                    .author("Max Korn <max.korn@tu-dresden.de>")
+                    .about("extracts a context-free grammar from a treebank in bracket notation (read from stdin)")
+                    .arg(Arg::with_name("parent-annotation")
+                         .help("annotates phrasal nonterminals with the labels of their parents")
+                         .long("parent-annotation")
+                         .required(false))
+                    .arg(Arg::with_name("markovisation")
+                         .help("binarises rules, remembering the given number of following siblings")
+                         .long("markovisation")
+                         .value_name("markovisation")
+                         .required(false))
+                    .arg(Arg::with_name("unknown-threshold")
+                         .help("replaces words that occur at most this often by UNK")
+                         .long("unknown-threshold")
+                         .value_name("unknown-threshold")
+                         .default_value("0")
+                         .required(false)))
 }
 
 pub fn handle_sub_matches(cfg_matches: &ArgMatches) {
@@ -52,29 +147,133 @@ pub fn handle_sub_matches(cfg_matches: &ArgMatches) {
             let mut grammar_string = String::new();
             let _ = grammar_file.read_to_string(&mut grammar_string);
             let grammar: CFG<String, String, LogDomain<f64>> = grammar_string.parse().unwrap();
+            let smoothing = cfg_parse_matches.value_of("smoothing").map(|l| LexicalSmoothing::from_factor(l.parse().unwrap()).unwrap());
+            let grammar = match smoothing {
+                Some(ref smoothing) => smooth_lexical_rules(&grammar, smoothing),
+                None => grammar,
+            };
+            let (grammar, mut lexicon) = if cfg_parse_matches.is_present("split-lexicon") {
+                let (grammar, lexicon) = split_lexicon(&grammar);
+                (grammar, Some(lexicon))
+            } else {
+                (grammar, None)
+            };
+            if let Some(lexicon_file_name) = cfg_parse_matches.value_of("lexicon") {
+                let mut lexicon_file = File::open(lexicon_file_name).unwrap();
+                let mut lexicon_string = String::new();
+                let _ = lexicon_file.read_to_string(&mut lexicon_string);
+                let file_lexicon: Lexicon<String, LogDomain<f64>> = lexicon_string.parse().unwrap();
+                lexicon = Some(match lexicon {
+                    Some(mut lexicon) => {
+                        lexicon.append(file_lexicon);
+                        lexicon
+                    },
+                    None => file_lexicon,
+                });
+            }
+            let tagged = cfg_parse_matches.is_present("tagged");
 
             let automaton = PushDownAutomaton::from(grammar);
 
+            let limits = SearchLimits {
+                max_items: cfg_parse_matches.value_of("max-items").map(|m| m.parse().unwrap()),
+                max_agenda_size: None,
+                timeout: cfg_parse_matches
+                    .value_of("timeout")
+                    .map(|t| Duration::from_millis(t.parse().unwrap())),
+            };
+
             let mut corpus = String::new();
             let _ = io::stdin().read_to_string(&mut corpus);
 
-            for sentence in corpus.lines() {
-                let word = sentence.split_whitespace().map(|x| x.to_string()).collect();
-                match cfg_parse_matches.value_of("beam-width") {
-                    Some(b) => {
-                        for parse in automaton
-                            .recognise_beam_search(b.parse().unwrap(), word)
-                            .take(n)
-                        {
-                            println!("{:?}", parse.0);
-                        }
-                    }
-                    None => {
-                        for parse in automaton.recognise(word).take(n) {
-                            println!("{:?}", parse.0);
-                        }
+            let beam = match (cfg_parse_matches.value_of("beam-threshold"), cfg_parse_matches.value_of("beam-width")) {
+                (Some(t), _) => Some(BeamMode::Threshold(LogDomain::new(t.parse().unwrap()).unwrap())),
+                (None, Some(b)) => {
+                    let b = b.parse().unwrap();
+                    match cfg_parse_matches.value_of("beam-mode") {
+                        Some("position") => Some(BeamMode::Position(b)),
+                        Some("key") => Some(BeamMode::StorageKey(b)),
+                        _ => Some(BeamMode::Width(b)),
                     }
+                },
+                (None, None) => None,
+            };
+            let stats = cfg_parse_matches.is_present("stats");
+            let threads = cfg_parse_matches.value_of("threads").unwrap().parse().unwrap();
+            let output_format = cfg_parse_matches.value_of("output").unwrap().to_string();
+            let lattices = cfg_parse_matches.is_present("lattice");
+            let inputs: Vec<String> = if lattices {
+                corpus
+                    .split("\n\n")
+                    .filter(|lattice| !lattice.trim().is_empty())
+                    .map(|lattice| lattice.to_string())
+                    .collect()
+            } else {
+                corpus.lines().map(|sentence| sentence.to_string()).collect()
+            };
+
+            let outputs = parse_batch(Arc::new(automaton), inputs, threads, move |automaton, input| {
+                let statistics = if stats {
+                    Some(Rc::new(RefCell::new(SearchStatistics::default())))
+                } else {
+                    None
+                };
+                let options = SearchOptions {
+                    statistics: statistics.clone(),
+                    limits: limits.clone(),
+                    ..SearchOptions::default()
+                };
+
+                let word: Vec<String> = input.split_whitespace().map(|x| x.to_string()).collect();
+                // the words that replace the preterminals at the leaves of the parses
+                let leaves = if tagged {
+                    Some(tagged_words(&word))
+                } else if lexicon.is_some() {
+                    Some(word.clone())
+                } else {
+                    None
+                };
+                let lattice = if lattices {
+                    Some(input.parse().map(|lattice: Lattice<String, LogDomain<f64>>| if smoothing.is_some() {
+                        lattice.map(&mut |t| replace_unknown_word(t.clone(), &|t| automaton.terminal_to_int(t).is_some()))
+                    } else {
+                        lattice
+                    }))
+                } else if tagged {
+                    Some(tagged_to_lattice(&word))
+                } else {
+                    lexicon.as_ref().map(|lexicon| Ok(lexicon.to_lattice(&word)))
                 };
+                if let Some(lattice) = lattice {
+                    let lattice = match lattice {
+                        Ok(lattice) => lattice,
+                        Err(reason) => return (format!("gave up: {}\n", reason), None),
+                    };
+                    let parses = match beam {
+                        Some(mode) => recognise_lattice_beam_mode_bounded(automaton, mode, lattice, options),
+                        None => recognise_lattice_bounded(automaton, lattice, options),
+                    };
+                    let output = format_parses(parses.map(|parse| parse.map(|(c, run)| (c.configuration, run))), n, &output_format, leaves.as_ref().map(|l| &l[..]));
+                    return (output, statistics.as_ref().map(|s| s.borrow().to_string()));
+                }
+
+                let word = if smoothing.is_some() {
+                    replace_unknown_words(word, |t| automaton.terminal_to_int(t).is_some())
+                } else {
+                    word
+                };
+                let parses = match beam {
+                    Some(mode) => recognise_beam_mode_bounded(automaton, mode, word, options),
+                    None => recognise_bounded(automaton, word, options),
+                };
+                (format_parses(parses, n, &output_format, None), statistics.as_ref().map(|s| s.borrow().to_string()))
+            });
+
+            for (output, statistics) in outputs {
+                print!("{}", output);
+                if let Some(statistics) = statistics {
+                    eprintln!("{}", statistics);
+                }
                 println!();
             }
         }
@@ -88,6 +287,55 @@ pub fn handle_sub_matches(cfg_matches: &ArgMatches) {
             let automaton = PushDownAutomaton::from(grammar);
             println!("{}", automaton);
         }
+        ("extract", Some(cfg_extract_matches)) => {
+            let options = ExtractionOptions {
+                parent_annotation: cfg_extract_matches.is_present("parent-annotation"),
+                markovisation: cfg_extract_matches.value_of("markovisation").map(|h| h.parse().unwrap()),
+                unknown_threshold: cfg_extract_matches.value_of("unknown-threshold").unwrap().parse().unwrap(),
+            };
+
+            let mut treebank_string = String::new();
+            let _ = io::stdin().read_to_string(&mut treebank_string);
+            let treebank = read_bracket_corpus(&treebank_string).unwrap();
+
+            print!("{}", extract_cfg(&treebank, &options));
+        }
         _ => (),
     }
 }
+
+/// Formats an accepting run and its final configuration in the given `output_format`.
+/// If `leaves` are given, they replace the terminal symbols of the tree.
+fn format_parse(
+    parse: &Item<PushDown<PushState<String, String>>, PushDownInstruction<PushState<String, String>>, String, LogDomain<f64>>,
+    output_format: &str,
+    leaves: Option<&[String]>,
+) -> String {
+    match output_format {
+        "derivation" => format!("{}", to_derivation(&parse.1)),
+        "bracket" => {
+            let derivation = to_derivation(&parse.1);
+            let derivation = match leaves {
+                Some(leaves) => replace_terminals(&derivation, leaves).unwrap_or(derivation),
+                None => derivation,
+            };
+            format!("{}\n", to_bracket(&derivation))
+        },
+        _ => format!("{:?}\n", parse.0),
+    }
+}
+
+/// Formats the first `n` of the `parses` in the given `output_format`, or the reason why the
+/// search was aborted.
+fn format_parses<P>(parses: P, n: usize, output_format: &str, leaves: Option<&[String]>) -> String
+    where P: Iterator<Item=Result<Item<PushDown<PushState<String, String>>, PushDownInstruction<PushState<String, String>>, String, LogDomain<f64>>, GaveUp>>,
+{
+    let mut output = String::new();
+    for parse in parses.take(n) {
+        match parse {
+            Ok(parse) => output.push_str(&format_parse(&parse, output_format, leaves)),
+            Err(reason) => output.push_str(&format!("gave up: {}\n", reason)),
+        }
+    }
+    output
+}
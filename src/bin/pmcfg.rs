@@ -1,11 +1,23 @@
 use clap::{Arg, ArgMatches, App, SubCommand};
 use log_domain::LogDomain;
-use rustomata::pmcfg::PMCFG;
-use rustomata::recognisable::Recognisable;
-use rustomata::tree_stack_automaton::TreeStackAutomaton;
+use rustomata::pmcfg::{PMCFG, PMCFGRule, replace_terminals, separate_terminal_rules, smooth_lexical_rules, split_lexicon, to_bracket, to_term};
+use rustomata::pmcfg::negra::to_negra;
+use rustomata::recognisable::Configuration;
+use rustomata::recognisable::{GaveUp, SearchLimits, SearchOptions, SearchStatistics};
+use rustomata::recognisable::automaton::{Automaton, BeamMode, recognise_beam_mode_bounded, recognise_bounded};
+use rustomata::recognisable::batch::parse_batch;
+use rustomata::recognisable::lattice::{Lattice, recognise_lattice_beam_mode_bounded, recognise_lattice_bounded};
+use rustomata::recognisable::lexicon::{Lexicon, tagged_to_lattice, tagged_words};
+use rustomata::tree_stack_automaton::{PosState, TreeStack, TreeStackAutomaton, to_derivation};
+use rustomata::util::tree::GornTree;
+use rustomata::util::unknown_words::{LexicalSmoothing, replace_unknown_word, replace_unknown_words, validate_factor};
 
+use std::cell::RefCell;
 use std::io::{self, Read};
 use std::fs::File;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn get_sub_command() -> App<'static, 'static> {
     SubCommand::with_name("mcfg")
@@ -37,6 +49,96 @@ pub fn get_sub_command() -> App<'static, 'static> {
                         .long("beam")
                         .value_name("beam-width")
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name("beam-mode")
+                        .help("pruning policy of the beam: the whole agenda (default), items with the same remaining word length, or items with the same storage key share a beam")
+                        .long("beam-mode")
+                        .value_name("beam-mode")
+                        .possible_values(&["width", "position", "key"])
+                        .requires("beam-width")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("beam-threshold")
+                        .help("drops items whose weight is less than the given factor (between 0 and 1) times the weight of the best item with the same remaining word length so far")
+                        .long("beam-threshold")
+                        .value_name("beam-threshold")
+                        .validator(validate_factor)
+                        .conflicts_with_all(&["beam-width", "beam-mode"])
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("max-items")
+                        .help("maximum number of items that are taken from the agenda for each sentence")
+                        .long("max-items")
+                        .value_name("max-items")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .help("maximum time (in milliseconds) that is spent searching for each sentence")
+                        .long("timeout")
+                        .value_name("timeout")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .help("number of threads that parse sentences in parallel")
+                        .long("threads")
+                        .value_name("threads")
+                        .default_value("1")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("smoothing")
+                        .help("handles unknown words: moves the given share of the weight of each lexical rule to rules for word signatures and UNK, and replaces unknown tokens by their signature or UNK")
+                        .long("smoothing")
+                        .value_name("smoothing")
+                        .validator(validate_factor)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("representation of the parses: the derivation of the grammar, its term, a NEGRA export, a tree in bracket notation, or the final configuration of the automaton")
+                        .long("output")
+                        .value_name("output")
+                        .possible_values(&["derivation", "term", "negra", "bracket", "configuration"])
+                        .default_value("configuration")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("stats")
+                        .help("prints statistics of the search for each sentence to stderr")
+                        .long("stats")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("lattice")
+                        .help("reads word lattices (separated by empty lines) instead of sentences")
+                        .long("lattice")
+                        .conflicts_with_all(&["tagged", "lexicon", "split-lexicon"])
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("tagged")
+                        .help("reads sentences of tokens of the form word/TAG and parses their tags")
+                        .long("tagged")
+                        .conflicts_with_all(&["lexicon", "split-lexicon"])
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("lexicon")
+                        .help("lexicon file that maps words to the preterminals of the grammar")
+                        .long("lexicon")
+                        .value_name("lexicon")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("split-lexicon")
+                        .help("moves the lexical rules of the grammar to a lexicon that is used for parsing")
+                        .long("split-lexicon")
+                        .required(false),
                 ),
         )
         .subcommand(
@@ -66,29 +168,137 @@ pub fn handle_sub_matches(mcfg_matches: &ArgMatches) {
             let _ = grammar_file.read_to_string(&mut grammar_string);
             let grammar: PMCFG<String, String, LogDomain<f64>> =
                 grammar_string.parse().unwrap();
+            let smoothing = mcfg_parse_matches
+                .value_of("smoothing")
+                .map(|l| LexicalSmoothing::from_factor(l.parse().unwrap()).unwrap());
+            let grammar = match smoothing {
+                Some(ref smoothing) => smooth_lexical_rules(&grammar, smoothing),
+                None => grammar,
+            };
+            let (grammar, mut lexicon) = if mcfg_parse_matches.is_present("split-lexicon") {
+                let (grammar, lexicon) = split_lexicon(&grammar);
+                (grammar, Some(lexicon))
+            } else {
+                (grammar, None)
+            };
+            if let Some(lexicon_file_name) = mcfg_parse_matches.value_of("lexicon") {
+                let mut lexicon_file = File::open(lexicon_file_name).unwrap();
+                let mut lexicon_string = String::new();
+                let _ = lexicon_file.read_to_string(&mut lexicon_string);
+                let file_lexicon: Lexicon<String, LogDomain<f64>> = lexicon_string.parse().unwrap();
+                lexicon = Some(match lexicon {
+                    Some(mut lexicon) => {
+                        lexicon.append(file_lexicon);
+                        lexicon
+                    },
+                    None => file_lexicon,
+                });
+            }
+            let tagged = mcfg_parse_matches.is_present("tagged");
 
             let automaton = TreeStackAutomaton::from(grammar);
 
+            let limits = SearchLimits {
+                max_items: mcfg_parse_matches.value_of("max-items").map(|m| m.parse().unwrap()),
+                max_agenda_size: None,
+                timeout: mcfg_parse_matches
+                    .value_of("timeout")
+                    .map(|t| Duration::from_millis(t.parse().unwrap())),
+            };
+
             let mut corpus = String::new();
             let _ = io::stdin().read_to_string(&mut corpus);
 
-            for sentence in corpus.lines() {
-                let word = sentence.split_whitespace().map(|x| x.to_string()).collect();
-                match mcfg_parse_matches.value_of("beam-width") {
-                    Some(b) => {
-                        for parse in automaton
-                            .recognise_beam_search(b.parse().unwrap(), word)
-                            .take(n)
-                        {
-                            println!("{}", parse.0);
-                        }
-                    }
-                    None => {
-                        for parse in automaton.recognise(word).take(n) {
-                            println!("{}", parse.0);
-                        }
+            let beam = match (mcfg_parse_matches.value_of("beam-threshold"), mcfg_parse_matches.value_of("beam-width")) {
+                (Some(t), _) => Some(BeamMode::Threshold(LogDomain::new(t.parse().unwrap()).unwrap())),
+                (None, Some(b)) => {
+                    let b = b.parse().unwrap();
+                    match mcfg_parse_matches.value_of("beam-mode") {
+                        Some("position") => Some(BeamMode::Position(b)),
+                        Some("key") => Some(BeamMode::StorageKey(b)),
+                        _ => Some(BeamMode::Width(b)),
                     }
+                },
+                (None, None) => None,
+            };
+            let stats = mcfg_parse_matches.is_present("stats");
+            let threads = mcfg_parse_matches.value_of("threads").unwrap().parse().unwrap();
+            let output_format = mcfg_parse_matches.value_of("output").unwrap().to_string();
+            let lattices = mcfg_parse_matches.is_present("lattice");
+            let inputs: Vec<(usize, String)> = if lattices {
+                corpus
+                    .split("\n\n")
+                    .filter(|lattice| !lattice.trim().is_empty())
+                    .map(|lattice| lattice.to_string())
+                    .enumerate()
+                    .collect()
+            } else {
+                corpus.lines().map(|sentence| sentence.to_string()).enumerate().collect()
+            };
+
+            let outputs = parse_batch(Arc::new(automaton), inputs, threads, move |automaton, (sentence_id, input)| {
+                let statistics = if stats {
+                    Some(Rc::new(RefCell::new(SearchStatistics::default())))
+                } else {
+                    None
+                };
+                let options = SearchOptions {
+                    statistics: statistics.clone(),
+                    limits: limits.clone(),
+                    ..SearchOptions::default()
                 };
+
+                let word: Vec<String> = input.split_whitespace().map(|x| x.to_string()).collect();
+                // the words that replace the preterminals at the leaves of the parses
+                let leaves = if tagged {
+                    Some(tagged_words(&word))
+                } else if lexicon.is_some() {
+                    Some(word.clone())
+                } else {
+                    None
+                };
+                let lattice = if lattices {
+                    Some(input.parse().map(|lattice: Lattice<String, LogDomain<f64>>| if smoothing.is_some() {
+                        lattice.map(&mut |t| replace_unknown_word(t.clone(), &|t| automaton.terminal_to_int(t).is_some()))
+                    } else {
+                        lattice
+                    }))
+                } else if tagged {
+                    Some(tagged_to_lattice(&word))
+                } else {
+                    lexicon.as_ref().map(|lexicon| Ok(lexicon.to_lattice(&word)))
+                };
+                if let Some(lattice) = lattice {
+                    let lattice = match lattice {
+                        Ok(lattice) => lattice,
+                        Err(reason) => return (format!("gave up: {}\n", reason), None),
+                    };
+                    let parses = match beam {
+                        Some(mode) => recognise_lattice_beam_mode_bounded(automaton, mode, lattice, options),
+                        None => recognise_lattice_bounded(automaton, lattice, options),
+                    };
+                    let output = format_parses(parses.map(|parse| parse.map(|(c, _)| c.configuration)), n, &output_format, sentence_id, leaves.as_ref().map(|l| &l[..]));
+                    return (output, statistics.as_ref().map(|s| s.borrow().to_string()));
+                }
+
+                let word = if smoothing.is_some() {
+                    replace_unknown_words(word, |t| automaton.terminal_to_int(t).is_some())
+                } else {
+                    word
+                };
+                let parses = match beam {
+                    Some(mode) => recognise_beam_mode_bounded(automaton, mode, word, options),
+                    None => recognise_bounded(automaton, word, options),
+                };
+                let output = format_parses(parses.map(|parse| parse.map(|(c, _)| c)), n, &output_format, sentence_id, None);
+                (output, statistics.as_ref().map(|s| s.borrow().to_string()))
+            });
+
+            for (output, statistics) in outputs {
+                print!("{}", output);
+                if let Some(statistics) = statistics {
+                    eprintln!("{}", statistics);
+                }
                 println!();
             }
         }
@@ -105,3 +315,45 @@ pub fn handle_sub_matches(mcfg_matches: &ArgMatches) {
         _ => (),
     }
 }
+
+/// Formats the final configurations of the first `n` of the `parses` in the given
+/// `output_format`, or the reason why the search was aborted.
+/// If `leaves` are given, they replace the terminal symbols of the trees.
+fn format_parses<P>(parses: P, n: usize, output_format: &str, sentence_id: usize, leaves: Option<&[String]>) -> String
+    where P: Iterator<Item=Result<Configuration<TreeStack<PosState<PMCFGRule<String, String, LogDomain<f64>>>>, String, LogDomain<f64>>, GaveUp>>,
+{
+    let mut output = String::new();
+    for parse in parses.take(n) {
+        match parse {
+            Ok(configuration) => output.push_str(&format_parse(&configuration, output_format, sentence_id, leaves)),
+            Err(reason) => output.push_str(&format!("gave up: {}\n", reason)),
+        }
+    }
+    output
+}
+
+/// Formats the final configuration of an accepting run in the given `output_format`.
+/// If `leaves` are given, they replace the terminal symbols of the term, NEGRA export or tree.
+fn format_parse(
+    configuration: &Configuration<TreeStack<PosState<PMCFGRule<String, String, LogDomain<f64>>>>, String, LogDomain<f64>>,
+    output_format: &str,
+    sentence_id: usize,
+    leaves: Option<&[String]>,
+) -> String {
+    let with_leaves = |derivation: GornTree<PMCFGRule<String, String, LogDomain<f64>>>| match leaves {
+        Some(leaves) => replace_terminals(&derivation, leaves).unwrap_or(derivation),
+        None => derivation,
+    };
+    let mut output = match output_format {
+        "derivation" => format!("{}", to_derivation(&configuration.storage)),
+        "term" => to_term(&with_leaves(to_derivation(&configuration.storage))).0.to_term_string(),
+        // the preterminals are separated before they are replaced
+        "negra" => to_negra(&with_leaves(separate_terminal_rules(&to_derivation(&configuration.storage))), sentence_id),
+        "bracket" => to_bracket(&with_leaves(to_derivation(&configuration.storage))),
+        _ => format!("{}", configuration),
+    };
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
@@ -4,10 +4,12 @@ use rustomata::recognisable::{Item, Recognisable};
 use rustomata::tree_stack_automaton::{TreeStackAutomaton, TreeStack, TreeStackInstruction};
 use rustomata::approximation::ApproximationStrategy;
 use rustomata::approximation::tts::TTSElement;
+use rustomata::recognisable::batch::parse_batch;
+use rustomata::recognisable::chart_pruning::ChartPruningRecogniser;
 use rustomata::recognisable::coarse_to_fine::CoarseToFineRecogniser;
 use std::fmt::Debug;
 use std::io::{self, Read};
-use std::rc::Rc;
+use std::sync::Arc;
 use std::fs::File;
 
 pub fn get_sub_command() -> App<'static, 'static> {
@@ -40,13 +42,36 @@ pub fn get_sub_command() -> App<'static, 'static> {
                         .default_value("1")
                         .required(false),
                 )
+                .arg(
+                    Arg::with_name("threads")
+                        .help("number of threads that recognise sentences in parallel")
+                        .long("threads")
+                        .value_name("threads")
+                        .default_value("1")
+                        .required(false),
+                )
                 .arg(
                     Arg::with_name("coarse-to-fine")
                         .help("use coarse-to-parsing")
                         .short("ctf")
                         .long("coarse-to-fine")
                         .value_name("strategies")
-                        .default_value("")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("chart-pruning")
+                        .help("prunes the items of the tts approximation whose posterior is below the threshold before recognising")
+                        .long("chart-pruning")
+                        .value_name("threshold")
+                        .conflicts_with("coarse-to-fine")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("chart-items")
+                        .help("maximum number of configurations of the tts approximation that are explored for the chart pruning")
+                        .long("chart-items")
+                        .value_name("chart-items")
+                        .default_value("100000")
                         .required(false),
                 ),
         )
@@ -82,38 +107,55 @@ pub fn handle_sub_matches(tsa_matches: &ArgMatches) {
                     None => None,
                 };
 
-            match tsa_recognise_matches.value_of("strategies") {
+            let threads = tsa_recognise_matches
+                .value_of("threads")
+                .unwrap()
+                .parse()
+                .unwrap();
+
+            if let Some(threshold) = tsa_recognise_matches.value_of("chart-pruning") {
+                let (coarse_automaton, instance) = TTSElement::new().approximate_automaton(&automaton);
+                let max_items = tsa_recognise_matches.value_of("chart-items").unwrap().parse().unwrap();
+                let rec = ChartPruningRecogniser::new(
+                    automaton, coarse_automaton, instance, threshold.parse().unwrap(), max_items
+                );
+
+                return recognise_corpus(rec, n, beam, threads, corpus);
+            }
+
+            match tsa_recognise_matches.value_of("coarse-to-fine") {
                 Some("tts") => {
                     let rec = coarse_to_fine_recogniser!(automaton; TTSElement::new());
 
-                    recognise_corpus(rec, n, beam, corpus)
+                    recognise_corpus(rec, n, beam, threads, corpus)
                 },
                 Some(e) => panic!("[ERR] Strategy \"{}\" unknown.", e),
-                None => recognise_corpus(automaton, n, beam, corpus)
+                None => recognise_corpus(automaton, n, beam, threads, corpus)
             }
         }
         _ => (),
     }
 }
 
-fn recognise_corpus<A, Rec, T, W>(rec: Rec, n: usize, beam: Option<usize>, corpus: Vec<Vec<T>>)
-    where Rec: Recognisable<T, W, Parse = Item<TreeStack<A>, TreeStackInstruction<A>, T, W>>,
-          A: Debug,
-          W: Debug,
-          T: Debug,
+fn recognise_corpus<A, Rec, T, W>(rec: Rec, n: usize, beam: Option<usize>, threads: usize, corpus: Vec<Vec<T>>)
+    where Rec: Recognisable<T, W, Parse = Item<TreeStack<A>, TreeStackInstruction<A>, T, W>> + Send + Sync + 'static,
+          A: Debug + 'static,
+          W: Debug + 'static,
+          T: Debug + Send + 'static,
 {
-    for sentence in corpus {
-        println!("{:?}:", sentence);
-        match beam {
-            Some(b) =>
-                for run in rec.recognise_beam_search(b, sentence).take(n) {
-                    println!("  {:?}", run.1);
-                },
-            None =>
-                for run in rec.recognise(sentence).take(n) {
-                    println!("  {:?}", run.1)
-                }
+    let outputs = parse_batch(Arc::new(rec), corpus, threads, move |rec, sentence| {
+        let mut output = format!("{:?}:\n", sentence);
+        let runs = match beam {
+            Some(b) => rec.recognise_beam_search(b, sentence),
+            None => rec.recognise(sentence),
+        };
+        for run in runs.take(n) {
+            output.push_str(&format!("  {:?}\n", run.1));
         }
-        println!();
+        output
+    });
+
+    for output in outputs {
+        println!("{}", output);
     }
 }
@@ -0,0 +1,278 @@
+use log_domain::LogDomain;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use cfg::{CFG, CFGComposition, CFGRule, LetterT, from_label_tree};
+use util::tree::GornTree;
+use util::unknown_words::UNKNOWN_WORD;
+
+/// Transformations that are applied to the trees of a treebank before a `CFG` is extracted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExtractionOptions {
+    /// annotates every phrasal nonterminal (i.e. neither the root nor a preterminal) `A` whose
+    /// parent is labelled `B` as `A^B`
+    pub parent_annotation: bool,
+    /// binarises rules with more than two symbols on their right-hand side, remembering the
+    /// given number of following siblings in the labels of the new nonterminals `A|<…>`
+    pub markovisation: Option<usize>,
+    /// replaces words that occur at most this often in the treebank by `UNKNOWN_WORD`
+    pub unknown_threshold: usize,
+}
+
+/// Constituent of a tree of the treebank; leaves are words.
+#[derive(Clone, Debug)]
+struct Constituent {
+    label: String,
+    children: Vec<Constituent>,
+}
+
+impl Constituent {
+    fn from_derivation(derivation: &GornTree<CFGRule<String, String, LogDomain<f64>>>,
+                       address: Vec<usize>)
+                       -> Constituent
+    {
+        let rule = derivation.get(&address).unwrap();
+        let mut children = Vec::new();
+        let mut next_nonterminal = 0;
+
+        for symbol in &rule.composition.composition {
+            match *symbol {
+                LetterT::Label(_) => {
+                    let mut child_address = address.clone();
+                    child_address.push(next_nonterminal);
+                    next_nonterminal += 1;
+                    children.push(Constituent::from_derivation(derivation, child_address));
+                },
+                LetterT::Value(ref word) => {
+                    children.push(Constituent { label: word.clone(), children: Vec::new() });
+                },
+            }
+        }
+
+        Constituent { label: rule.head.clone(), children }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn is_preterminal(&self) -> bool {
+        !self.is_leaf() && self.children.iter().all(Constituent::is_leaf)
+    }
+
+    fn count_words(&self, counts: &mut HashMap<String, usize>) {
+        if self.is_leaf() {
+            *counts.entry(self.label.clone()).or_insert(0) += 1;
+        }
+        for child in &self.children {
+            child.count_words(counts);
+        }
+    }
+
+    fn replace_rare_words(&mut self, counts: &HashMap<String, usize>, threshold: usize) {
+        if self.is_leaf() && counts.get(&self.label).map_or(true, |&c| c <= threshold) {
+            self.label = UNKNOWN_WORD.to_string();
+        }
+        for child in &mut self.children {
+            child.replace_rare_words(counts, threshold);
+        }
+    }
+
+    fn annotate_parents(&mut self, parent: Option<&str>) {
+        if self.is_leaf() {
+            return;
+        }
+        let label = self.label.clone();
+        for child in &mut self.children {
+            child.annotate_parents(Some(label.as_str()));
+        }
+        if let Some(parent) = parent {
+            if !self.is_preterminal() {
+                self.label = format!("{}^{}", self.label, parent);
+            }
+        }
+    }
+
+    fn symbol(&self) -> LetterT<String, String> {
+        if self.is_leaf() {
+            LetterT::Value(self.label.clone())
+        } else {
+            LetterT::Label(self.label.clone())
+        }
+    }
+
+    /// Counts the rules used in the constituent (after binarisation with the given markovisation).
+    fn count_rules(&self,
+                   markovisation: Option<usize>,
+                   counts: &mut BTreeMap<String, BTreeMap<CFGComposition<String, String>, usize>>)
+    {
+        if self.is_leaf() {
+            return;
+        }
+
+        let symbols: Vec<_> = self.children.iter().map(Constituent::symbol).collect();
+        let mut head = self.label.clone();
+        let mut start = 0;
+
+        if let Some(h) = markovisation {
+            while symbols.len() - start > 2 {
+                let context: Vec<String> = symbols[start + 1..]
+                    .iter()
+                    .take(h)
+                    .map(|s| match *s {
+                        LetterT::Label(ref x) | LetterT::Value(ref x) => x.clone(),
+                    })
+                    .collect();
+                let intermediate = format!("{}|<{}>", self.label, context.join(","));
+                let composition = vec![symbols[start].clone(), LetterT::Label(intermediate.clone())];
+                count_rule(counts, head, composition);
+                head = intermediate;
+                start += 1;
+            }
+        }
+        count_rule(counts, head, symbols[start..].to_vec());
+
+        for child in &self.children {
+            child.count_rules(markovisation, counts);
+        }
+    }
+}
+
+fn count_rule(counts: &mut BTreeMap<String, BTreeMap<CFGComposition<String, String>, usize>>,
+              head: String,
+              composition: Vec<LetterT<String, String>>)
+{
+    *counts
+        .entry(head)
+        .or_insert_with(BTreeMap::new)
+        .entry(CFGComposition::from(composition))
+        .or_insert(0) += 1;
+}
+
+/// Extracts a `CFG` from the given trees (e.g. read by `util::tree::read_bracket_corpus`), where
+/// each rule is weighted by its relative frequency among the rules with the same left-hand side.
+/// The labels of the roots of the trees are the initial nonterminals.
+/// Traces and co-indices are removed from the trees, cf. `cfg::from_label_tree`.
+///
+/// ```
+/// use rustomata::cfg::extraction::{ExtractionOptions, extract_cfg};
+/// use rustomata::util::tree::read_bracket_corpus;
+///
+/// let treebank = read_bracket_corpus("(S (A a) (B b))\n(S (A a))").unwrap();
+/// let grammar = extract_cfg(&treebank, &ExtractionOptions::default());
+///
+/// assert_eq!(vec!["S".to_string()], grammar.initial);
+/// assert_eq!(4, grammar.rules.len());
+/// ```
+pub fn extract_cfg(treebank: &[GornTree<String>], options: &ExtractionOptions)
+                   -> CFG<String, String, LogDomain<f64>>
+{
+    let mut trees: Vec<Constituent> = treebank
+        .iter()
+        .map(from_label_tree::<LogDomain<f64>>)
+        .filter(|derivation| !derivation.is_empty())
+        .map(|derivation| Constituent::from_derivation(&derivation, Vec::new()))
+        .collect();
+
+    if options.unknown_threshold > 0 {
+        let mut word_counts = HashMap::new();
+        for tree in &trees {
+            tree.count_words(&mut word_counts);
+        }
+        for tree in &mut trees {
+            tree.replace_rare_words(&word_counts, options.unknown_threshold);
+        }
+    }
+
+    if options.parent_annotation {
+        for tree in &mut trees {
+            tree.annotate_parents(None);
+        }
+    }
+
+    let mut initial = BTreeSet::new();
+    let mut counts = BTreeMap::new();
+    for tree in &trees {
+        initial.insert(tree.label.clone());
+        tree.count_rules(options.markovisation, &mut counts);
+    }
+
+    let mut rules = Vec::new();
+    for (head, compositions) in counts {
+        let total: usize = compositions.values().sum();
+        for (composition, count) in compositions {
+            rules.push(CFGRule {
+                head: head.clone(),
+                composition,
+                weight: LogDomain::new(count as f64 / total as f64).unwrap(),
+            });
+        }
+    }
+
+    CFG { initial: initial.into_iter().collect(), rules }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::tree::read_bracket_corpus;
+
+    fn rule(s: &str) -> CFGRule<String, String, LogDomain<f64>> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_extract_cfg() {
+        let treebank = read_bracket_corpus("(S (A a) (B b))\n(S (A a))\n(S (A a) (B b))").unwrap();
+        let grammar = extract_cfg(&treebank, &ExtractionOptions::default());
+
+        let expected = vec![
+            ("A → [T a]", 1.0),
+            ("B → [T b]", 1.0),
+            ("S → [Nt A]", 1.0 / 3.0),
+            ("S → [Nt A, Nt B]", 2.0 / 3.0),
+        ];
+        assert_eq!(expected.len(), grammar.rules.len());
+        for (r, (s, weight)) in grammar.rules.iter().zip(expected) {
+            assert_eq!(&rule(s), r);
+            assert_eq!(LogDomain::new(weight).unwrap(), r.weight);
+        }
+
+        let reparsed: CFG<String, String, LogDomain<f64>> = grammar.to_string().parse().unwrap();
+        assert_eq!(grammar.initial, reparsed.initial);
+        assert_eq!(grammar.rules, reparsed.rules);
+    }
+
+    #[test]
+    fn test_extract_cfg_transformations() {
+        let treebank = read_bracket_corpus("(S (NP (D the) (N dog)) (VP (V barks) (A loudly) (P now)))").unwrap();
+
+        let options = ExtractionOptions { parent_annotation: true, ..ExtractionOptions::default() };
+        let grammar = extract_cfg(&treebank, &options);
+        assert!(grammar.rules.contains(&rule("NP^S → [Nt D, Nt N]")));
+        assert!(grammar.rules.contains(&rule("D → [T the]")));
+
+        let options = ExtractionOptions { markovisation: Some(1), ..ExtractionOptions::default() };
+        let grammar = extract_cfg(&treebank, &options);
+        assert!(grammar.rules.contains(&rule("VP → [Nt V, Nt \"VP|<A>\"]")));
+        assert!(grammar.rules.contains(&rule("\"VP|<A>\" → [Nt A, Nt P]")));
+
+        // the labels introduced by the transformations can be read again
+        let options = ExtractionOptions {
+            parent_annotation: true,
+            markovisation: Some(1),
+            ..ExtractionOptions::default()
+        };
+        let grammar = extract_cfg(&treebank, &options);
+        assert!(grammar.rules.contains(&rule("\"VP^S|<A>\" → [Nt A, Nt P]")));
+        let reparsed: CFG<String, String, LogDomain<f64>> = grammar.to_string().parse().unwrap();
+        assert_eq!(grammar.initial, reparsed.initial);
+        assert_eq!(grammar.rules, reparsed.rules);
+
+        let treebank = read_bracket_corpus("(S (A a) (A b))\n(S (A a))").unwrap();
+        let options = ExtractionOptions { unknown_threshold: 1, ..ExtractionOptions::default() };
+        let grammar = extract_cfg(&treebank, &options);
+        assert!(grammar.rules.contains(&rule("A → [T a]")));
+        assert!(grammar.rules.contains(&rule("A → [T UNK]")));
+        assert!(!grammar.rules.contains(&rule("A → [T b]")));
+    }
+}
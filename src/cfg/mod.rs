@@ -1,6 +1,16 @@
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
+use num_traits::{One, Zero};
+use std::collections::BTreeSet;
+use std::ops::{AddAssign, Mul};
+use std::slice;
+
+use recognisable::lexicon::Lexicon;
+use util::tree::{ConstituentChild, GornTree};
+use util::unknown_words::{LexicalSmoothing, smooth_lexicon};
+
+pub mod extraction;
 mod from_str;
 mod from_pmcfg;
 
@@ -124,3 +134,327 @@ impl<N: fmt::Display, T: fmt::Display, W: fmt::Display> fmt::Display for CFGRule
         write!(f, "\"{}\" → {}  # {}", self.head, self.composition, self.weight)
     }
 }
+
+impl<N: fmt::Display, T: fmt::Display, W: fmt::Display> fmt::Display for CFG<N, T, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buffer = "".to_string();
+
+        let mut iter = self.initial.iter().peekable();
+
+        buffer.push_str("initial: [");
+        while let Some(nt) = iter.next() {
+            buffer.push_str(format!("\"{}\"", nt).as_str());
+            if iter.peek().is_some() {
+                buffer.push_str(", ");
+            }
+        }
+        buffer.push_str("]\n\n");
+
+        for r in &self.rules {
+            buffer.push_str(format!("{}\n", r).as_str());
+        }
+
+        write!(f, "{}", buffer)
+    }
+}
+
+/// Turns a derivation (where the `i`th child of a node is the derivation of the `i`th nonterminal
+/// in the composition of its rule) into a constituent tree whose inner nodes are labelled with the
+/// heads of the rules and whose leaves are the terminal symbols.
+pub fn to_label_tree<N, T, W>(derivation: &GornTree<CFGRule<N, T, W>>) -> GornTree<String>
+    where N: fmt::Display,
+          T: fmt::Display,
+{
+    GornTree::from_constituents(Vec::new(), |address: &Vec<usize>| {
+        let rule = derivation.get(address)?;
+        let mut next_nonterminal = 0;
+
+        let children = rule.composition.composition.iter().map(|symbol| match *symbol {
+            LetterT::Label(_) => {
+                let mut child_address = address.clone();
+                child_address.push(next_nonterminal);
+                next_nonterminal += 1;
+                ConstituentChild::Constituent(child_address)
+            },
+            LetterT::Value(ref terminal) => ConstituentChild::Leaf(terminal.to_string()),
+        }).collect();
+
+        Some((rule.head.to_string(), children))
+    })
+}
+
+/// Writes a derivation in (Penn-style) bracket notation, cf. `to_label_tree`.
+pub fn to_bracket<N, T, W>(derivation: &GornTree<CFGRule<N, T, W>>) -> String
+    where N: fmt::Display,
+          T: fmt::Display,
+{
+    to_label_tree(derivation).to_bracket()
+}
+
+/// Replaces the terminal symbols of a derivation (from left to right) by the given `words`, e.g.
+/// to put the words of a sentence back at the leaves after its tags have been parsed.
+///
+/// ```
+/// use rustomata::cfg::{CFGRule, replace_terminals, to_bracket};
+/// use rustomata::util::tree::GornTree;
+///
+/// let mut derivation: GornTree<CFGRule<String, String, f64>> = GornTree::new();
+/// derivation.insert(vec![], "S → [Nt NP, T V]".parse().unwrap());
+/// derivation.insert(vec![0], "NP → [T D, T N]".parse().unwrap());
+///
+/// let words: Vec<_> = vec!["the", "dog", "sleeps"].into_iter().map(String::from).collect();
+/// let replaced = replace_terminals(&derivation, &words).unwrap();
+/// assert_eq!("(S (NP the dog) sleeps)", to_bracket(&replaced));
+/// assert!(replace_terminals(&derivation, &words[1..]).is_err());
+/// ```
+pub fn replace_terminals<N, T, W>(derivation: &GornTree<CFGRule<N, T, W>>, words: &[T])
+        -> Result<GornTree<CFGRule<N, T, W>>, String>
+    where N: Clone,
+          T: Clone,
+          W: Clone,
+{
+    let mut replaced = GornTree::new();
+    let mut remaining_words = words.iter();
+    if derivation.contains_key(&Vec::new()) {
+        replace_terminals_at(derivation, Vec::new(), &mut remaining_words, &mut replaced)?;
+    }
+    match remaining_words.len() {
+        0 => Ok(replaced),
+        n => Err(format!("{} of the {} words are not derived.", n, words.len())),
+    }
+}
+
+fn replace_terminals_at<'a, N, T, W>(derivation: &GornTree<CFGRule<N, T, W>>,
+                                     address: Vec<usize>,
+                                     words: &mut slice::Iter<'a, T>,
+                                     replaced: &mut GornTree<CFGRule<N, T, W>>)
+                                     -> Result<(), String>
+    where N: Clone,
+          T: Clone + 'a,
+          W: Clone,
+{
+    let rule = derivation.get(&address).unwrap();
+    let mut composition = Vec::new();
+    let mut child = 0;
+
+    for symbol in &rule.composition.composition {
+        match *symbol {
+            LetterT::Label(ref nonterminal) => {
+                let mut child_address = address.clone();
+                child_address.push(child);
+                child += 1;
+                replace_terminals_at(derivation, child_address, words, replaced)?;
+                composition.push(LetterT::Label(nonterminal.clone()));
+            },
+            LetterT::Value(_) => match words.next() {
+                Some(word) => composition.push(LetterT::Value(word.clone())),
+                None => return Err("The derivation yields more terminal symbols than words are given.".to_string()),
+            },
+        }
+    }
+
+    replaced.insert(address, CFGRule {
+        head: rule.head.clone(),
+        composition: CFGComposition::from(composition),
+        weight: rule.weight.clone(),
+    });
+    Ok(())
+}
+
+/// Turns a constituent tree (e.g. read by `GornTree::from_bracket`) into a derivation with one
+/// rule (of weight `W::one()`) per inner node, i.e. leaves become terminal symbols.
+/// Traces (subtrees labelled `-NONE-`) and constituents that only contain traces are removed,
+/// and co-indices (`A-k` for a number `k`) are stripped from the labels.
+///
+/// ```
+/// use rustomata::cfg::{CFGRule, from_label_tree};
+/// use rustomata::util::tree::GornTree;
+///
+/// let tree = GornTree::from_bracket("(S (NP-1 a) (VP b (-NONE- *T*-1)))").unwrap();
+/// let derivation = from_label_tree::<f64>(&tree);
+///
+/// assert_eq!(
+///     &"S → [Nt NP, Nt VP]".parse::<CFGRule<String, String, f64>>().unwrap(),
+///     derivation.get(&vec![]).unwrap()
+/// );
+/// assert_eq!(
+///     &"VP → [T b]".parse::<CFGRule<String, String, f64>>().unwrap(),
+///     derivation.get(&vec![1]).unwrap()
+/// );
+/// ```
+pub fn from_label_tree<W>(tree: &GornTree<String>) -> GornTree<CFGRule<String, String, W>>
+    where W: One,
+{
+    let mut derivation = GornTree::new();
+    if tree.contains_key(&Vec::new()) && !tree.is_trace(&[]) {
+        insert_rules(tree, Vec::new(), &mut derivation, Vec::new());
+    }
+    derivation
+}
+
+/// Reads a derivation from (Penn-style) bracket notation, cf. `from_label_tree`.
+pub fn from_bracket<W>(s: &str) -> Result<GornTree<CFGRule<String, String, W>>, String>
+    where W: One,
+{
+    GornTree::from_bracket(s).map(|tree| from_label_tree(&tree))
+}
+
+fn insert_rules<W>(tree: &GornTree<String>,
+                   tree_address: Vec<usize>,
+                   derivation: &mut GornTree<CFGRule<String, String, W>>,
+                   address: Vec<usize>)
+    where W: One,
+{
+    let mut composition = Vec::new();
+
+    for i in tree.child_positions(&tree_address) {
+        let mut child_tree_address = tree_address.clone();
+        child_tree_address.push(i);
+        if tree.is_trace(&child_tree_address) {
+            continue;
+        }
+        let label = tree.get(&child_tree_address).unwrap();
+        if tree.child_positions(&child_tree_address).is_empty() {
+            // the leaf of an empty constituent is not a terminal symbol
+            if !label.is_empty() {
+                composition.push(LetterT::Value(label.clone()));
+            }
+        } else {
+            let mut child_address = address.clone();
+            child_address.push(composition.iter().filter(|s| match **s {
+                LetterT::Label(_) => true,
+                LetterT::Value(_) => false,
+            }).count());
+            composition.push(LetterT::Label(strip_coindex(label)));
+            insert_rules(tree, child_tree_address, derivation, child_address);
+        }
+    }
+
+    let head = strip_coindex(tree.get(&tree_address).unwrap());
+    derivation.insert(address, CFGRule {
+        head,
+        composition: CFGComposition::from(composition),
+        weight: W::one(),
+    });
+}
+
+fn strip_coindex(label: &str) -> String {
+    match label.rfind('-') {
+        Some(i) if i > 0 && i + 1 < label.len() && label[i + 1..].chars().all(|c| c.is_digit(10))
+            => label[..i].to_string(),
+        _ => label.to_string(),
+    }
+}
+
+/// Smooths the lexical rules `A → [T w]` of the `grammar` with `util::unknown_words::smooth_lexicon`,
+/// i.e. adds rules for the signatures of the words and for `UNK`.
+pub fn smooth_lexical_rules<N, W>(grammar: &CFG<N, String, W>, smoothing: &LexicalSmoothing<W>)
+                                  -> CFG<N, String, W>
+    where N: Clone + Ord,
+          W: AddAssign + Copy + Mul<Output=W> + Zero,
+{
+    let mut lexicon = Vec::new();
+    let mut rules = Vec::new();
+
+    for rule in &grammar.rules {
+        match rule.composition.composition.first() {
+            Some(&LetterT::Value(ref word)) if rule.composition.composition.len() == 1 => {
+                lexicon.push((rule.head.clone(), word.clone(), rule.weight))
+            },
+            _ => rules.push(rule.clone()),
+        }
+    }
+
+    for (head, word, weight) in smooth_lexicon(&lexicon, smoothing) {
+        rules.push(CFGRule {
+            head,
+            composition: CFGComposition::from(vec![LetterT::Value(word)]),
+            weight,
+        });
+    }
+
+    CFG { initial: grammar.initial.clone(), rules }
+}
+
+/// Splits the lexical rules off the `grammar`, such that sentences can be parsed with a
+/// `Lexicon` (cf. `recognisable::lexicon`).
+/// Terminal symbols `a` in other rules are first separated, i.e. replaced by a new preterminal
+/// `a` (extended by `a` until it differs from all nonterminals) with the lexical rule `a → [T a]`.
+/// Every lexical rule `A → [T w]` with weight `p` becomes the entry `(w, A, p)` of the returned
+/// `Lexicon` and is replaced by the rule `A → [T A]` with weight one, i.e. the returned grammar
+/// reads preterminals instead of words.
+///
+/// ```
+/// use rustomata::cfg::{CFG, split_lexicon};
+///
+/// let grammar: CFG<String, String, f64> = "initial: [S]\n\
+///                                          S → [T a, Nt N]\n\
+///                                          N → [T dog] # 0.5\n\
+///                                          N → [T cat] # 0.5".parse().unwrap();
+/// let (grammar, lexicon) = split_lexicon(&grammar);
+///
+/// assert!(grammar.rules.contains(&"S → [Nt a, Nt N]".parse().unwrap()));
+/// assert!(grammar.rules.contains(&"a → [T a]".parse().unwrap()));
+/// assert!(grammar.rules.contains(&"N → [T N]".parse().unwrap()));
+/// assert_eq!(&[("N".to_string(), 0.5)], lexicon.get("dog"));
+/// assert_eq!(&[("a".to_string(), 1.0)], lexicon.get("a"));
+/// ```
+pub fn split_lexicon<W>(grammar: &CFG<String, String, W>) -> (CFG<String, String, W>, Lexicon<String, W>)
+    where W: Clone + One,
+{
+    let mut nonterminals = BTreeSet::new();
+    for rule in &grammar.rules {
+        nonterminals.insert(rule.head.clone());
+        for symbol in &rule.composition.composition {
+            if let LetterT::Label(ref nonterminal) = *symbol {
+                nonterminals.insert(nonterminal.clone());
+            }
+        }
+    }
+
+    let mut lexicon = Lexicon::new();
+    let mut preterminals = BTreeSet::new();
+    let mut terminals = BTreeSet::new();
+    let mut rules = Vec::new();
+
+    for rule in &grammar.rules {
+        match rule.composition.composition.first() {
+            Some(&LetterT::Value(ref word)) if rule.composition.composition.len() == 1 => {
+                lexicon.insert(word.clone(), rule.head.clone(), rule.weight.clone());
+                preterminals.insert(rule.head.clone());
+            },
+            _ => {
+                let composition: Vec<_> = rule.composition.composition.iter().map(|symbol| match *symbol {
+                    LetterT::Value(ref terminal) => {
+                        let mut preterminal = terminal.clone();
+                        while nonterminals.contains(&preterminal) {
+                            preterminal.push_str(terminal);
+                        }
+                        terminals.insert((terminal.clone(), preterminal.clone()));
+                        preterminals.insert(preterminal.clone());
+                        LetterT::Label(preterminal)
+                    },
+                    LetterT::Label(ref nonterminal) => LetterT::Label(nonterminal.clone()),
+                }).collect();
+                rules.push(CFGRule {
+                    head: rule.head.clone(),
+                    composition: CFGComposition::from(composition),
+                    weight: rule.weight.clone(),
+                });
+            },
+        }
+    }
+
+    for (terminal, preterminal) in terminals {
+        lexicon.insert(terminal, preterminal, W::one());
+    }
+    for preterminal in preterminals {
+        rules.push(CFGRule {
+            head: preterminal.clone(),
+            composition: CFGComposition::from(vec![LetterT::Value(preterminal)]),
+            weight: W::one(),
+        });
+    }
+
+    (CFG { initial: grammar.initial.clone(), rules }, lexicon)
+}
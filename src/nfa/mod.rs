@@ -1,7 +1,10 @@
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::ops::{AddAssign, Mul, MulAssign};
+use std::rc::Rc;
+use std::time::Instant;
 
 use num_traits::{One, Zero};
 
@@ -46,6 +49,10 @@ impl<S: Eq + Hash + Ord + Clone, T: Eq + Hash + Clone + Ord, W: Eq + Ord + One +
     }
 
     pub fn recognise(&self, word: &[T]) -> NFARecogniser<S, T, W> {
+        self.recognise_with_options(word, SearchOptions::default())
+    }
+
+    pub fn recognise_with_options(&self, word: &[T], options: SearchOptions) -> NFARecogniser<S, T, W> {
         let mut init_heap = BinaryHeap::new();
         for i in self.initial_states.clone(){
             let c = Configuration {
@@ -60,6 +67,7 @@ impl<S: Eq + Hash + Ord + Clone, T: Eq + Hash + Clone + Ord, W: Eq + Ord + One +
             filtered_rules: self.transitions.clone(),
             accepting: self.final_states.clone(),
             //used: HashSet::new(),
+            statistics: options.statistics,
         }
     }
 }
@@ -147,6 +155,7 @@ pub struct NFARecogniser<S: Clone + Ord + Hash + Eq, T: Eq + Hash, W: Eq + Ord>
     filtered_rules: HashMap<S, BinaryHeap<NFATransition<S, T, W>>>,
     accepting: HashSet<S>,
     //used: HashSet<Configuration<S, T, W>>,
+    statistics: Option<Rc<RefCell<SearchStatistics>>>,
 }
 
 impl<S: Clone + Ord + Hash + Eq, T: Eq + Hash, W: Eq + Ord> NFARecogniser<S, T, W> {
@@ -159,25 +168,32 @@ impl<S: Clone + Ord + Hash + Eq, T: Eq + Hash + Clone + Ord, W: One + Mul<Output
     type Item = (Configuration<S, T, W>, Vec<NFATransition<S, T, W>>);
 
     fn next(&mut self) -> Option<(Configuration<S, T, W>, Vec<NFATransition<S, T, W>>)> {
+        let start = Instant::now();
         while let Some((c, run)) = self.agenda.pop() {
             //self.used.insert(c.clone());
+            SearchStatistics::record(&self.statistics, |s| s.dequeued += 1);
             if let Some(rs) = self.filtered_rules.get(&(c.storage)) {
                 for r in rs {
+                    SearchStatistics::record(&self.statistics, |s| s.transitions_applied += 1);
                     let cv = r.apply(&c);
                     for c1 in cv{
                         //if !self.used.contains(&c1){
                         let mut run1 = run.clone();
                         run1.push(r.clone());
-                        self.agenda.push((c1, run1))
+                        self.agenda.push((c1, run1));
+                        let size = self.agenda.len();
+                        SearchStatistics::record(&self.statistics, |s| s.record_enqueue(size, false));
                         //}
                     }
                 }
             }
             if self.accepts(&c) {
+                SearchStatistics::record(&self.statistics, |s| s.duration += start.elapsed());
                 return Some((c, run));
             }
         }
 
+        SearchStatistics::record(&self.statistics, |s| s.duration += start.elapsed());
         None
     }
 }
@@ -0,0 +1,37 @@
+use pmcfg::*;
+use cfg::{LetterT, CFG};
+
+impl<N: Clone, T: Clone, W: Clone> From<CFG<N, T, W>> for PMCFG<N, T, W> {
+    fn from(cfg: CFG<N, T, W>) -> PMCFG<N, T, W> {
+        let mut rules = Vec::new();
+
+        for r in cfg.rules {
+            let mut tail = Vec::new();
+            let mut component = Vec::new();
+
+            for symbol in &r.composition.composition {
+                match *symbol {
+                    LetterT::Value(ref x) => {
+                        component.push(VarT::T(x.clone()));
+                    },
+                    LetterT::Label(ref x) => {
+                        component.push(VarT::Var(tail.len(), 0));
+                        tail.push(x.clone());
+                    },
+                }
+            }
+
+            rules.push(PMCFGRule {
+                head: r.head,
+                tail,
+                composition: Composition::from(vec![component]),
+                weight: r.weight,
+            });
+        }
+
+        PMCFG {
+            initial: cfg.initial,
+            rules,
+        }
+    }
+}
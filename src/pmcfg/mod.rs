@@ -1,14 +1,20 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use num_traits::{One, Zero};
 use std::iter::Extend;
+use std::ops::{AddAssign, Mul};
 use std::slice;
 use std::vec;
 
-use util::tree::GornTree;
+use recognisable::lexicon::Lexicon;
+use util::tree::{ConstituentChild, GornTree};
+use util::unknown_words::{LexicalSmoothing, smooth_lexicon};
 
+mod from_cfg;
 mod from_str;
 pub mod negra;
+pub mod relabel;
 
 /// Variable or terminal symbol in a PMCFG.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
@@ -297,6 +303,344 @@ pub fn to_term<H, T, W>(tree_map: &GornTree<PMCFGRule<H, T, W>>)
     (term_map, head_map)
 }
 
+/// Replaces the terminal symbols of a derivation by the given `words` such that the derived word
+/// becomes `words`, e.g. to put the words of a sentence back at the leaves after its tags have
+/// been parsed.
+/// Terminal symbols that do not occur in the derived word are kept.
+///
+/// ```
+/// use std::str::FromStr;
+/// use rustomata::pmcfg::*;
+/// use rustomata::util::tree::GornTree;
+///
+/// let mut derivation: GornTree<PMCFGRule<String, String, f64>> = GornTree::new();
+/// derivation.insert(vec![], PMCFGRule::from_str("S → [[Var 0 0, T V, Var 0 1]] (A)").unwrap());
+/// derivation.insert(vec![0], PMCFGRule::from_str("A → [[T D], [T N]] ()").unwrap());
+///
+/// let words: Vec<_> = vec!["the", "sleeps", "dog"].into_iter().map(String::from).collect();
+/// let replaced = replace_terminals(&derivation, &words).unwrap();
+/// assert_eq!("(S (A-1 the) sleeps (A=1 dog))", to_bracket(&replaced));
+/// assert!(replace_terminals(&derivation, &words[1..]).is_err());
+/// ```
+pub fn replace_terminals<N, T, W>(derivation: &GornTree<PMCFGRule<N, T, W>>, words: &[T])
+        -> Result<GornTree<PMCFGRule<N, T, W>>, String>
+    where N: Clone,
+          T: Clone,
+          W: Clone,
+{
+    // number the occurrences of terminal symbols and derive the word of these numbers
+    let mut next_number = 0..;
+    let mut numbered_terms = GornTree::new();
+    for (address, rule) in derivation {
+        let composition: Vec<Vec<VarT<usize>>> = rule.composition.composition.iter().map(
+            |component| component.iter().map(|symbol| match symbol {
+                &VarT::Var(i, j) => VarT::Var(i, j),
+                &VarT::T(_) => VarT::T(next_number.next().unwrap()),
+            }).collect()
+        ).collect();
+        numbered_terms.insert(address.clone(), Composition::from(composition));
+    }
+
+    let mut positions = HashMap::new();
+    for symbol in evaluate(&numbered_terms).composition.into_iter().flat_map(|c| c) {
+        if let VarT::T(number) = symbol {
+            let position = positions.len();
+            positions.entry(number).or_insert(position);
+        }
+    }
+    if positions.len() != words.len() {
+        return Err(format!(
+            "The derivation yields {} terminal symbols, but {} words are given.",
+            positions.len(), words.len()
+        ));
+    }
+
+    let mut next_number = 0..;
+    let mut replaced = GornTree::new();
+    for (address, rule) in derivation {
+        let composition: Vec<Vec<VarT<T>>> = rule.composition.composition.iter().map(
+            |component| component.iter().map(|symbol| match symbol {
+                &VarT::Var(i, j) => VarT::Var(i, j),
+                &VarT::T(ref terminal) => match positions.get(&next_number.next().unwrap()) {
+                    Some(&position) => VarT::T(words[position].clone()),
+                    None => VarT::T(terminal.clone()),
+                },
+            }).collect()
+        ).collect();
+        replaced.insert(address.clone(), PMCFGRule {
+            head: rule.head.clone(),
+            tail: rule.tail.clone(),
+            composition: Composition::from(composition),
+            weight: rule.weight.clone(),
+        });
+    }
+
+    Ok(replaced)
+}
+
+/// Turns a derivation into a constituent tree whose inner nodes are labelled with the heads of
+/// the rules and whose leaves are the terminal symbols, such that the leaves are the derived word
+/// (from left to right).
+///
+/// Each component of a discontinuous constituent (i.e. of a rule with fan-out greater than 1)
+/// becomes a node at its own position in the word: the first one (from left to right) is
+/// labelled `A-k` with a fresh co-index `k` (starting at 1), every further one is labelled `A=k`.
+/// Components that are not used by the parent rule are omitted.
+/// Note that this encoding differs from the traces `(-NONE- *T*-k)` of the Penn Treebank: no
+/// component is distinguished as the filler of the others.
+/// The head `A` of a continuous constituent that already ends with `-k` or `=k` (for a number
+/// `k`) is written as `A-0`, such that it is not read as a co-indexed component.
+///
+/// ```
+/// use std::str::FromStr;
+/// use rustomata::pmcfg::*;
+/// use rustomata::util::tree::GornTree;
+///
+/// let mut derivation: GornTree<PMCFGRule<String, String, f64>> = GornTree::new();
+/// derivation.insert(vec![], PMCFGRule::from_str("S → [[Var 0 0, T b, Var 0 1]] (A)").unwrap());
+/// derivation.insert(vec![0], PMCFGRule::from_str("A → [[T a], [T c]] ()").unwrap());
+///
+/// assert_eq!("(S (A-1 a) b (A=1 c))", to_label_tree(&derivation).to_bracket());
+///
+/// derivation.insert(vec![], PMCFGRule::from_str("\"S-2\" → [[Var 0 0, Var 0 1]] (A)").unwrap());
+/// assert_eq!("(S-2-0 (A-1 a) (A=1 c))", to_label_tree(&derivation).to_bracket());
+/// ```
+pub fn to_label_tree<N, T, W>(derivation: &GornTree<PMCFGRule<N, T, W>>) -> GornTree<String>
+    where N: fmt::Display,
+          T: fmt::Display,
+{
+    let mut coindices: BTreeMap<Vec<usize>, usize> = BTreeMap::new();
+
+    GornTree::from_constituents((Vec::new(), 0), |&(ref address, j): &(Vec<usize>, usize)| {
+        let rule = derivation.get(address)?;
+        let component = rule.composition.composition.get(j)?;
+
+        let label = if rule.composition.composition.len() > 1 {
+            let coindex = coindices.get(address).cloned();
+            match coindex {
+                Some(k) => format!("{}={}", rule.head, k),
+                None => {
+                    let k = coindices.len() + 1;
+                    coindices.insert(address.clone(), k);
+                    format!("{}-{}", rule.head, k)
+                },
+            }
+        } else {
+            let head = rule.head.to_string();
+            if split_coindex(&head).0 == head {
+                head
+            } else {
+                format!("{}-0", head)
+            }
+        };
+
+        let children = component.iter().map(|symbol| match symbol {
+            &VarT::Var(i, k) => {
+                let mut child_address = address.clone();
+                child_address.push(i);
+                ConstituentChild::Constituent((child_address, k))
+            },
+            &VarT::T(ref terminal) => ConstituentChild::Leaf(terminal.to_string()),
+        }).collect();
+
+        Some((label, children))
+    })
+}
+
+/// Writes a derivation in bracket notation, cf. `to_label_tree`.
+///
+/// ```
+/// use std::str::FromStr;
+/// use rustomata::pmcfg::*;
+/// use rustomata::util::tree::GornTree;
+///
+/// let mut derivation: GornTree<PMCFGRule<String, String, f64>> = GornTree::new();
+/// derivation.insert(vec![], PMCFGRule::from_str("S → [[T a, Var 0 0]] (A)").unwrap());
+/// derivation.insert(vec![0], PMCFGRule::from_str("A → [[T b]] ()").unwrap());
+///
+/// assert_eq!("(S a (A b))", to_bracket(&derivation));
+/// ```
+pub fn to_bracket<N, T, W>(derivation: &GornTree<PMCFGRule<N, T, W>>) -> String
+    where N: fmt::Display,
+          T: fmt::Display,
+{
+    to_label_tree(derivation).to_bracket()
+}
+
+/// Turns a constituent tree (e.g. read by `GornTree::from_bracket`) into a derivation with one
+/// rule of weight `W::one()` for each constituent, cf. `to_label_tree`.
+/// The components `A-k` and `A=k` (with the same co-index `k`) form one constituent, the suffix
+/// of a label `A-0` is removed, and traces (`-NONE-`) are omitted.
+///
+/// ```
+/// use rustomata::pmcfg::*;
+/// use rustomata::util::tree::GornTree;
+///
+/// let tree = GornTree::from_bracket("(S (A-1 a) b (A=1 c))").unwrap();
+/// let derivation = from_label_tree::<f64>(&tree).unwrap();
+///
+/// assert_eq!(
+///     &"S → [[Var 0 0, T b, Var 0 1]] (A)".parse::<PMCFGRule<String, String, f64>>().unwrap(),
+///     derivation.get(&vec![]).unwrap()
+/// );
+/// assert_eq!(
+///     &"A → [[T a], [T c]] ()".parse::<PMCFGRule<String, String, f64>>().unwrap(),
+///     derivation.get(&vec![0]).unwrap()
+/// );
+/// assert!(from_label_tree::<f64>(&GornTree::from_bracket("(S (A=1 a))").unwrap()).is_err());
+/// ```
+pub fn from_label_tree<W>(tree: &GornTree<String>) -> Result<GornTree<PMCFGRule<String, String, W>>, String>
+    where W: One,
+{
+    let mut derivation = GornTree::new();
+    if tree.contains_key(&Vec::new()) && !tree.is_trace(&[]) {
+        let mut constituents = Vec::new();
+        let mut node_constituents = BTreeMap::new();
+        collect_constituents(tree, Vec::new(), None, &mut constituents,
+                             &mut node_constituents, &mut HashMap::new())?;
+        insert_rules(tree, &constituents, &node_constituents, 0, &mut derivation, Vec::new());
+    }
+    Ok(derivation)
+}
+
+/// Reads a derivation from bracket notation, cf. `from_label_tree`.
+pub fn from_bracket<W>(s: &str) -> Result<GornTree<PMCFGRule<String, String, W>>, String>
+    where W: One,
+{
+    from_label_tree(&GornTree::from_bracket(s)?)
+}
+
+/// A constituent of a constituent tree with the positions of its components.
+struct LabelTreeConstituent {
+    head: String,
+    parent: Option<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+/// Assigns the inner node at `address` and its descendants to their constituents (in pre-order).
+fn collect_constituents(tree: &GornTree<String>,
+                        address: Vec<usize>,
+                        parent: Option<usize>,
+                        constituents: &mut Vec<LabelTreeConstituent>,
+                        node_constituents: &mut BTreeMap<Vec<usize>, usize>,
+                        coindices: &mut HashMap<usize, usize>)
+                        -> Result<(), String>
+{
+    let label = tree.get(&address).unwrap();
+    let (head, coindex) = split_coindex(label);
+
+    let constituent = match coindex {
+        Some(('=', k)) => {
+            let c = match coindices.get(&k) {
+                Some(&c) => c,
+                None => return Err(format!("No constituent with co-index {} precedes '{}'.", k, label)),
+            };
+            if constituents[c].head != head || constituents[c].parent != parent {
+                return Err(format!("The component '{}' does not match its constituent.", label));
+            }
+            constituents[c].components.push(address.clone());
+            c
+        },
+        _ => {
+            let c = constituents.len();
+            if let Some((_, k)) = coindex {
+                coindices.insert(k, c);
+            }
+            constituents.push(LabelTreeConstituent {
+                head,
+                parent,
+                components: vec![address.clone()],
+            });
+            c
+        },
+    };
+    node_constituents.insert(address.clone(), constituent);
+
+    for i in tree.child_positions(&address) {
+        let mut child_address = address.clone();
+        child_address.push(i);
+        if !tree.is_trace(&child_address) && !tree.child_positions(&child_address).is_empty() {
+            collect_constituents(tree, child_address, Some(constituent), constituents,
+                                 node_constituents, coindices)?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_rules<W>(tree: &GornTree<String>,
+                   constituents: &[LabelTreeConstituent],
+                   node_constituents: &BTreeMap<Vec<usize>, usize>,
+                   constituent: usize,
+                   derivation: &mut GornTree<PMCFGRule<String, String, W>>,
+                   address: Vec<usize>)
+    where W: One,
+{
+    let mut successors: Vec<usize> = Vec::new();
+    let mut composition = Vec::new();
+
+    for node in &constituents[constituent].components {
+        let mut component = Vec::new();
+        for i in tree.child_positions(node) {
+            let mut child_node = node.clone();
+            child_node.push(i);
+            if tree.is_trace(&child_node) {
+                continue;
+            }
+            match node_constituents.get(&child_node) {
+                Some(&c) => {
+                    let position = successors.iter().position(|&s| s == c);
+                    let successor = match position {
+                        Some(successor) => successor,
+                        None => {
+                            successors.push(c);
+                            successors.len() - 1
+                        },
+                    };
+                    let j = constituents[c].components.iter().position(|n| n == &child_node).unwrap();
+                    component.push(VarT::Var(successor, j));
+                },
+                None => {
+                    // the leaf of an empty constituent is not a terminal symbol
+                    let label = tree.get(&child_node).unwrap();
+                    if !label.is_empty() {
+                        component.push(VarT::T(label.clone()));
+                    }
+                },
+            }
+        }
+        composition.push(component);
+    }
+
+    for (i, &c) in successors.iter().enumerate() {
+        let mut child_address = address.clone();
+        child_address.push(i);
+        insert_rules(tree, constituents, node_constituents, c, derivation, child_address);
+    }
+
+    derivation.insert(address, PMCFGRule {
+        head: constituents[constituent].head.clone(),
+        tail: successors.iter().map(|&c| constituents[c].head.clone()).collect(),
+        composition: Composition::from(composition),
+        weight: W::one(),
+    });
+}
+
+/// Splits a label `A-k` or `A=k` (cf. `to_label_tree`) into `A` and the separator and co-index.
+/// The label `A-0` is split into `A` without a co-index.
+fn split_coindex(label: &str) -> (String, Option<(char, usize)>) {
+    if let Some(i) = label.rfind(|c: char| c == '-' || c == '=') {
+        if i > 0 && &label[i..] == "-0" {
+            return (label[..i].to_string(), None);
+        }
+        if i > 0 && i + 1 < label.len() {
+            if let Ok(k) = label[i + 1..].parse() {
+                return (label[..i].to_string(), label[i..].chars().next().map(|c| (c, k)));
+            }
+        }
+    }
+    (label.to_string(), None)
+}
+
 /// Takes a tree stack _(encoded in a Gorn tree)_ of PMCFG rules of arbitrary form, and transforms
 /// it into a tree stack of PMCFG rules of a normal form, requiring each rule to be of one of the
 /// following forms:
@@ -363,83 +707,234 @@ pub fn separate_terminal_rules<HT, W>(tree_map: &GornTree<PMCFGRule<HT, HT, W>>)
         old_heads.push(head.clone());
     }
 
-    for (address, &PMCFGRule { ref head, ref tail, ref composition, ref weight }) in tree_map {
-        let mut next_child_num = tail.len()..;
-        let mut terminal_child_num = HashMap::new();
-        let mut terminal_children = Vec::new();
-        let mut new_composition = Vec::new();
-        let mut first_variable = true;
-        let mut contains_only_one_terminal = false;
-
-        for component in composition {
-            let mut new_component = Vec::new();
-
-            for variable in component {
-                match variable {
-                    &VarT::Var(num_nonter, num_compon) => {
-                        new_component.push(VarT::Var(num_nonter, num_compon));
-                    },
-                    &VarT::T(ref terminal) => {
-                        contains_only_one_terminal = first_variable;
-
-                        let child_num = if terminal_child_num.contains_key(terminal) {
-                            *terminal_child_num.get(terminal).unwrap()
-                        } else {
-                            let number = next_child_num.next().unwrap();
-                            terminal_child_num.insert(terminal.clone(), number);
-                            terminal_children.push(terminal.clone());
-                            number
-                        };
-
-                        new_component.push(VarT::Var(child_num, 0));
-                    },
-                }
+    for (address, rule) in tree_map {
+        let (new_rule, terminal_rules) = separate_terminal_rule(rule, &old_heads);
+        let first_terminal_child = new_rule.tail.len() - terminal_rules.len();
+
+        for (i, terminal_rule) in terminal_rules.into_iter().enumerate() {
+            let mut child_address = address.clone();
+            child_address.push(first_terminal_child + i);
+            new_tree.insert(child_address, terminal_rule);
+        }
+
+        new_tree.insert(address.clone(), new_rule);
+    }
 
-                first_variable = false;
+    new_tree
+}
+
+/// Transforms a single PMCFG rule into the normal form described for `separate_terminal_rules`.
+/// Unless the rule consists of exactly one terminal symbol, every terminal symbol `a` is replaced
+/// by a new successor whose rule `a → [[T a]] ()` (with the weight of the given rule) is returned
+/// alongside, in the order of the new successors.
+/// The heads of the new rules are extended by `a` until they differ from all `old_heads`.
+pub fn separate_terminal_rule<HT, W>(rule: &PMCFGRule<HT, HT, W>, old_heads: &[HT])
+        -> (PMCFGRule<HT, HT, W>, Vec<PMCFGRule<HT, HT, W>>)
+    where HT: Clone + Eq + Extend<HT> + Hash,
+          W: Clone,
+{
+    let &PMCFGRule { ref head, ref tail, ref composition, ref weight } = rule;
+    let mut next_child_num = tail.len()..;
+    let mut terminal_child_num = HashMap::new();
+    let mut terminal_children = Vec::new();
+    let mut new_composition = Vec::new();
+
+    for component in composition {
+        let mut new_component = Vec::new();
+
+        for variable in component {
+            match variable {
+                &VarT::Var(num_nonter, num_compon) => {
+                    new_component.push(VarT::Var(num_nonter, num_compon));
+                },
+                &VarT::T(ref terminal) => {
+                    let child_num = if terminal_child_num.contains_key(terminal) {
+                        *terminal_child_num.get(terminal).unwrap()
+                    } else {
+                        let number = next_child_num.next().unwrap();
+                        terminal_child_num.insert(terminal.clone(), number);
+                        terminal_children.push(terminal.clone());
+                        number
+                    };
+
+                    new_component.push(VarT::Var(child_num, 0));
+                },
             }
+        }
+
+        new_composition.push(new_component);
+    }
+
+    let contains_only_one_terminal = composition.composition.len() == 1
+        && composition.composition[0].len() == 1
+        && !terminal_children.is_empty();
+    if contains_only_one_terminal {
+        return (rule.clone(), Vec::new());
+    }
 
-            new_composition.push(new_component);
+    let mut unique_terminal_children = Vec::new();
+    let mut terminal_rules = Vec::new();
+
+    for mut terminal in terminal_children {
+        let original_terminal = terminal.clone();
+
+        while old_heads.contains(&terminal) {
+            terminal.extend(vec![original_terminal.clone()]);
         }
 
-        let new_rule = if contains_only_one_terminal {
-            PMCFGRule {
-                head: head.clone(), tail: tail.clone(), composition: composition.clone(),
-                weight: weight.clone()
-            }
-        } else {
-            let mut unique_terminal_children = Vec::new();
+        unique_terminal_children.push(terminal.clone());
+        terminal_rules.push(PMCFGRule {
+            head: terminal, tail: Vec::new(), composition: Composition::from(vec![
+                vec![VarT::T(original_terminal)]
+            ]), weight: weight.clone()
+        });
+    }
+
+    let mut new_tail = tail.clone();
+    new_tail.append(&mut unique_terminal_children);
 
-            for mut terminal in terminal_children {
-                let original_terminal = terminal.clone();
+    let new_rule = PMCFGRule {
+        head: head.clone(), tail: new_tail, composition: Composition::from(new_composition),
+        weight: weight.clone()
+    };
 
-                while old_heads.contains(&terminal) {
-                    terminal.extend(vec![original_terminal.clone()]);
+    (new_rule, terminal_rules)
+}
+
+/// Smooths the lexical rules `A → [[T w]] ()` of the `grammar` with
+/// `util::unknown_words::smooth_lexicon`, i.e. adds rules for the signatures of the words and for
+/// `UNK`.
+///
+/// ```
+/// use rustomata::pmcfg::{PMCFG, PMCFGRule, smooth_lexical_rules};
+/// use rustomata::util::unknown_words::LexicalSmoothing;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[Var 0 0]] (N)\n\
+///                                            N → [[T Dogs]] ()".parse().unwrap();
+/// let smoothing = LexicalSmoothing { known: 0.5, signature: 0.25, unknown: 0.25 };
+/// let smoothed = smooth_lexical_rules(&grammar, &smoothing);
+///
+/// assert_eq!(4, smoothed.rules.len());
+/// assert!(smoothed.rules.contains(&"N → [[T \"UNK-C-gs\"]] () # 0.25".parse().unwrap()));
+/// ```
+pub fn smooth_lexical_rules<N, W>(grammar: &PMCFG<N, String, W>, smoothing: &LexicalSmoothing<W>)
+                                  -> PMCFG<N, String, W>
+    where N: Clone + Ord,
+          W: AddAssign + Copy + Mul<Output=W> + Zero,
+{
+    let mut lexicon = Vec::new();
+    let mut rules = Vec::new();
+
+    for rule in &grammar.rules {
+        let word = match rule.composition.composition.first() {
+            Some(component) if rule.tail.is_empty() && rule.composition.composition.len() == 1
+                               && component.len() == 1 => {
+                match component[0] {
+                    VarT::T(ref word) => Some(word.clone()),
+                    VarT::Var(_, _) => None,
                 }
+            },
+            _ => None,
+        };
+        match word {
+            Some(word) => lexicon.push((rule.head.clone(), word, rule.weight)),
+            None => rules.push(rule.clone()),
+        }
+    }
 
-                unique_terminal_children.push(terminal.clone());
+    for (head, word, weight) in smooth_lexicon(&lexicon, smoothing) {
+        rules.push(PMCFGRule {
+            head,
+            tail: Vec::new(),
+            composition: Composition::from(vec![vec![VarT::T(word)]]),
+            weight,
+        });
+    }
 
-                let mut child_address = address.clone();
-                child_address.push(*terminal_child_num.get(&original_terminal).unwrap());
-                new_tree.insert(child_address, PMCFGRule {
-                    head: terminal, tail: Vec::new(), composition: Composition::from(vec![
-                        vec![VarT::T(original_terminal)]
-                    ]), weight: weight.clone()
-                });
-            }
+    PMCFG { initial: grammar.initial.clone(), rules }
+}
 
-            let mut new_tail = tail.clone();
-            new_tail.append(&mut unique_terminal_children);
+/// Splits the lexical rules off the `grammar`, such that sentences can be parsed with a
+/// `Lexicon` (cf. `recognisable::lexicon`).
+/// Terminal symbols in other rules are first separated with `separate_terminal_rule`.
+/// Every lexical rule `A → [[T w]] ()` with weight `p` becomes the entry `(w, A, p)` of the
+/// returned `Lexicon` and is replaced by the rule `A → [[T A]] ()` with weight one, i.e. the
+/// returned grammar reads preterminals instead of words.
+///
+/// ```
+/// use rustomata::pmcfg::{PMCFG, split_lexicon};
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[T a, Var 0 0]] (N)\n\
+///                                            S → [[Var 0 0, T a]] (N)\n\
+///                                            N → [[T dog]] () # 0.5\n\
+///                                            N → [[T cat]] () # 0.5".parse().unwrap();
+/// let (grammar, lexicon) = split_lexicon(&grammar);
+///
+/// assert!(grammar.rules.contains(&"S → [[Var 1 0, Var 0 0]] (N, a)".parse().unwrap()));
+/// assert!(grammar.rules.contains(&"S → [[Var 0 0, Var 1 0]] (N, a)".parse().unwrap()));
+/// assert!(grammar.rules.contains(&"N → [[T N]] ()".parse().unwrap()));
+/// assert_eq!(&[("N".to_string(), 0.5)], lexicon.get("dog"));
+/// assert_eq!(&[("a".to_string(), 1.0)], lexicon.get("a"));
+/// ```
+pub fn split_lexicon<W>(grammar: &PMCFG<String, String, W>)
+                        -> (PMCFG<String, String, W>, Lexicon<String, W>)
+    where W: Clone + One,
+{
+    let mut nonterminals = Vec::new();
+    for rule in &grammar.rules {
+        nonterminals.push(rule.head.clone());
+        nonterminals.extend(rule.tail.iter().cloned());
+    }
+
+    let mut lexicon = Lexicon::new();
+    let mut preterminals = BTreeSet::new();
+    let mut terminals = BTreeSet::new();
+    let mut rules = Vec::new();
+
+    for rule in &grammar.rules {
+        if let Some(word) = lexical_terminal(rule) {
+            lexicon.insert(word.clone(), rule.head.clone(), rule.weight.clone());
+            preterminals.insert(rule.head.clone());
+            continue;
+        }
 
-            PMCFGRule {
-                head: head.clone(), tail: new_tail, composition: Composition::from(new_composition),
-                weight: weight.clone()
+        let (new_rule, terminal_rules) = separate_terminal_rule(rule, &nonterminals);
+        for terminal_rule in terminal_rules {
+            if let Some(word) = lexical_terminal(&terminal_rule) {
+                terminals.insert((word.clone(), terminal_rule.head.clone()));
             }
-        };
+            preterminals.insert(terminal_rule.head);
+        }
+        rules.push(new_rule);
+    }
 
-        new_tree.insert(address.clone(), new_rule);
+    for (terminal, preterminal) in terminals {
+        lexicon.insert(terminal, preterminal, W::one());
+    }
+    for preterminal in preterminals {
+        rules.push(PMCFGRule {
+            head: preterminal.clone(),
+            tail: Vec::new(),
+            composition: Composition::from(vec![vec![VarT::T(preterminal)]]),
+            weight: W::one(),
+        });
     }
 
-    new_tree
+    (PMCFG { initial: grammar.initial.clone(), rules }, lexicon)
+}
+
+/// Returns `w` if `rule` is of the form `A → [[T w]] ()`.
+fn lexical_terminal<N, T, W>(rule: &PMCFGRule<N, T, W>) -> Option<&T> {
+    if !rule.tail.is_empty() || rule.composition.composition.len() != 1 {
+        return None;
+    }
+    let component = &rule.composition.composition[0];
+    match component.first() {
+        Some(&VarT::T(ref word)) if component.len() == 1 => Some(word),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
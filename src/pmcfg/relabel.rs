@@ -1,23 +1,65 @@
+use std::collections::HashMap;
 use std::hash::Hash;
 
-pub use util::*;
-pub use approximation::equivalence_classes::EquivalenceClass;
+use approximation::equivalence_classes::EquivalenceRelation;
+use approximation::grammar::RuleMap;
+use pmcfg::{PMCFG, PMCFGRule};
 
-pub use approximation::relabel::*;
-pub use pmcfg::*;
+/// Relabels the nonterminals of the `grammar` with their classes in the `relation`.
+/// Fine rules that become equal are merged into one coarse rule with the greatest of their
+/// weights, so that every coarse derivation is at least as heavy as the fine derivations it
+/// approximates.
+/// Returns the coarse grammar and the fine rules that each coarse rule approximates.
+///
+/// ```
+/// use rustomata::approximation::equivalence_classes::EquivalenceRelation;
+/// use rustomata::pmcfg::PMCFG;
+/// use rustomata::pmcfg::relabel::relabel;
+///
+/// let grammar: PMCFG<String, String, f64> = "initial: [S]\n\
+///                                            S → [[Var 0 0]] (A)\n\
+///                                            S → [[Var 0 0]] (B) # 0.5\n\
+///                                            A → [[T a]] ()\n\
+///                                            B → [[T a]] ()".parse().unwrap();
+/// let relation: EquivalenceRelation<String, String> = "N [A, B]\nS *".parse().unwrap();
+/// let (coarse_grammar, rule_map) = relabel(&grammar, &relation);
+///
+/// assert_eq!(2, coarse_grammar.rules.len());
+/// assert_eq!(2, rule_map[&coarse_grammar.rules[0]].len());
+/// assert_eq!(1.0, coarse_grammar.rules[0].weight);
+/// ```
+pub fn relabel<N1, N2, T, W>(grammar: &PMCFG<N1, T, W>, relation: &EquivalenceRelation<N1, N2>)
+                             -> (PMCFG<N2, T, W>, RuleMap<PMCFGRule<N2, T, W>, PMCFGRule<N1, T, W>>)
+    where N1: Clone + Eq + Hash,
+          N2: Clone + Eq + Hash,
+          T: Clone + Eq + Hash,
+          W: Clone + PartialOrd,
+{
+    let mut rules: Vec<PMCFGRule<N2, T, W>> = Vec::new();
+    let mut fine_rules: Vec<Vec<PMCFGRule<N1, T, W>>> = Vec::new();
+    let mut index = HashMap::new();
 
-impl<N1: Clone + Eq + Hash, N2: Clone + Eq + Hash, T: Clone, W: Clone> Relabel<N1, N2, PMCFGRule<N2, T, W>> for PMCFGRule<N1, T, W>{
-        fn relabel(&self, mapping: &EquivalenceClass<N1, N2>) -> PMCFGRule<N2, T, W> {
-            let mut new_tail = Vec::new();
-            for t in self.tail.clone(){
-                new_tail.push(mapping.project(&t).clone());
-            }
+    for rule in &grammar.rules {
+        let coarse_rule = rule.map_nonterminals(|nt| relation.project(nt));
+        let i = *index.entry(coarse_rule.clone()).or_insert(rules.len());
 
-            PMCFGRule {
-                head: mapping.project(&self.head).clone(),
-                tail: new_tail,
-                composition: self.composition.clone(),
-                weight: self.weight.clone(),
-            }
+        if i == rules.len() {
+            rules.push(coarse_rule);
+            fine_rules.push(Vec::new());
+        } else if rules[i].weight < rule.weight {
+            rules[i].weight = rule.weight.clone();
         }
+        fine_rules[i].push(rule.clone());
+    }
+
+    let mut initial = Vec::new();
+    for nt in &grammar.initial {
+        let coarse_nt = relation.project(nt);
+        if !initial.contains(&coarse_nt) {
+            initial.push(coarse_nt);
+        }
+    }
+
+    let rule_map = rules.iter().cloned().zip(fine_rules).collect();
+    (PMCFG { initial, rules }, rule_map)
 }
@@ -11,6 +11,8 @@ use std::str::FromStr;
 use recognisable::Transition;
 use cfg::*;
 use push_down_automaton::{PushDown, PushDownAutomaton, PushDownInstruction};
+use util::push_down::Pushdown;
+use util::tree::GornTree;
 
 /// Symbols of a `PushDown` created by an `CFG`
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
@@ -130,6 +132,78 @@ impl<N: Clone + Ord + PartialEq + Hash,
     }
 }
 
+/// Returns the derivation of a `CFG` that corresponds to an accepting run of the
+/// `PushDownAutomaton` constructed from it.
+/// The `i`th child of a node is the derivation of the `i`th nonterminal in the composition of
+/// its rule.
+///
+/// ```
+/// extern crate log_domain;
+/// extern crate rustomata;
+///
+/// use log_domain::LogDomain;
+/// use rustomata::cfg::{CFG, to_bracket};
+/// use rustomata::push_down_automaton::{PushDownAutomaton, to_derivation};
+/// use rustomata::recognisable::Recognisable;
+///
+/// # fn main() {
+/// let grammar: CFG<String, String, LogDomain<f64>> = "initial: [S]\n\
+///                                                     S → [T a, Nt A] # 1\n\
+///                                                     A → [T b] # 1".parse().unwrap();
+/// let automaton = PushDownAutomaton::from(grammar);
+/// let parse = automaton.recognise(vec!["a".to_string(), "b".to_string()]).next().unwrap();
+///
+/// let derivation = to_derivation(&parse.1);
+/// assert_eq!("S", derivation.get(&vec![]).unwrap().head);
+/// assert_eq!("A", derivation.get(&vec![0]).unwrap().head);
+/// assert_eq!("(S a (A b))", to_bracket(&derivation));
+/// # }
+/// ```
+pub fn to_derivation<N, T, W>(run: &Pushdown<Transition<PushDownInstruction<PushState<N, T>>, T, W>>)
+                              -> GornTree<CFGRule<N, T, W>>
+    where N: Clone,
+          T: Clone,
+          W: Clone,
+{
+    let mut derivation = GornTree::new();
+    // addresses of the nonterminals that have not been derived yet, the leftmost one on top
+    let mut open_addresses = vec![Vec::new()];
+
+    for transition in run.iter() {
+        let PushDownInstruction::Replace { ref current_val, ref new_val } = transition.instruction;
+        let head = match current_val.first() {
+            Some(&PushState::Nt(ref head)) if current_val.len() == 1 => head.clone(),
+            _ => continue,
+        };
+        let address = open_addresses.pop().expect("The given run derives more nonterminals than it introduces!");
+
+        let mut composition = Vec::new();
+        let mut children = Vec::new();
+        for symbol in new_val.iter().rev() {
+            match *symbol {
+                PushState::Nt(ref x) => {
+                    let mut child = address.clone();
+                    child.push(children.len());
+                    children.push(child);
+                    composition.push(LetterT::Label(x.clone()));
+                },
+                PushState::T(ref x) => composition.push(LetterT::Value(x.clone())),
+                _ => panic!("The given run contains a transition that was not constructed from a CFG!"),
+            }
+        }
+        children.reverse();
+        open_addresses.append(&mut children);
+
+        derivation.insert(address, CFGRule {
+            head,
+            composition: CFGComposition::from(composition),
+            weight: transition.weight.clone(),
+        });
+    }
+
+    derivation
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
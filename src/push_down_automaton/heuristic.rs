@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Mul;
+
+use num_traits::{One, Zero};
+
+use recognisable::{Configuration, Transition};
+use push_down_automaton::{PushDown, PushDownInstruction};
+use util::agenda::Heuristic;
+use util::push_down::Pushdown;
+
+/// `Heuristic` for (integerised) `PushDownAutomaton`s that estimates the weight of a
+/// `Configuration` by the best weights with which the symbols on its stack (except for the bottom
+/// symbol) can be removed from the stack.
+/// The remaining word is ignored.
+///
+/// The estimate is admissible if no weight is greater than `W::one()` (e.g. for probabilities).
+/// Otherwise, a cycle of transitions may improve the weight of a symbol indefinitely, and the
+/// estimate is cut off after one round per symbol (like in the Bellman-Ford algorithm).
+#[derive(Debug, Clone)]
+pub struct StackHeuristic<W> {
+    inside: HashMap<usize, W>,
+}
+
+impl<W> StackHeuristic<W>
+    where W: Copy + Mul<Output=W> + One + Ord + Zero,
+{
+    /// Computes the best weight to remove each symbol by iterating the `transitions` until a
+    /// fixpoint is reached, but for at most as many rounds as there are symbols.
+    /// Symbols that are removed together with other symbols are estimated by `W::one()`.
+    pub fn new<'a, It>(transitions: It) -> Self
+        where It: IntoIterator<Item=&'a Transition<PushDownInstruction<usize>, usize, W>>,
+              W: 'a,
+    {
+        let mut unbounded = HashSet::new();
+        let mut symbols = HashSet::new();
+        let mut rules = Vec::new();
+        for t in transitions {
+            match t.instruction {
+                PushDownInstruction::Replace { ref current_val, ref new_val } => {
+                    symbols.extend(current_val.iter().cloned());
+                    symbols.extend(new_val.iter().cloned());
+                    if current_val.len() == 1 {
+                        rules.push((current_val[0], new_val.clone(), t.weight));
+                    } else {
+                        unbounded.extend(current_val.iter().cloned());
+                    }
+                },
+            }
+        }
+
+        let mut inside = HashMap::new();
+        for &a in &unbounded {
+            inside.insert(a, W::one());
+        }
+
+        // with weights of at most one, the best way to remove a symbol does not remove the same
+        // symbol again, hence it is found after one round per symbol
+        let mut changed = true;
+        let mut rounds = 0;
+        while changed && rounds < symbols.len() {
+            changed = false;
+            rounds += 1;
+            for &(a, ref new_val, weight) in &rules {
+                if unbounded.contains(&a) {
+                    continue;
+                }
+                let w = new_val.iter().fold(
+                    weight,
+                    |acc, b| acc * inside.get(b).cloned().unwrap_or_else(W::zero)
+                );
+                if inside.get(&a).map_or(true, |&old| w > old) {
+                    inside.insert(a, w);
+                    changed = true;
+                }
+            }
+        }
+
+        StackHeuristic { inside }
+    }
+
+    /// Returns the estimate for the given stack.
+    pub fn estimate_stack(&self, storage: &PushDown<usize>) -> W {
+        storage.iter().skip(1).fold(
+            W::one(),
+            |acc, a| acc * self.inside.get(a).cloned().unwrap_or_else(W::zero)
+        )
+    }
+}
+
+impl<W, R> Heuristic<(Configuration<PushDown<usize>, usize, W>, Pushdown<R>)> for StackHeuristic<W>
+    where W: Copy + Mul<Output=W> + One + Ord + Zero,
+{
+    type Weight = W;
+
+    fn estimate(&self, item: &(Configuration<PushDown<usize>, usize, W>, Pushdown<R>)) -> W {
+        self.estimate_stack(&item.0.storage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_heuristic_weights_greater_than_one() {
+        // the cycle 0 → 0 improves the estimate of 0 in every round
+        let transitions = vec![
+            Transition {
+                word: Vec::new(),
+                weight: 2,
+                instruction: PushDownInstruction::Replace { current_val: vec![0], new_val: vec![0] },
+            },
+            Transition {
+                word: vec![0],
+                weight: 1,
+                instruction: PushDownInstruction::Replace { current_val: vec![0], new_val: Vec::new() },
+            },
+        ];
+        let heuristic = StackHeuristic::new(&transitions);
+
+        assert!(heuristic.estimate_stack(&PushDown::from(vec![1, 0])) >= 1);
+    }
+}
@@ -6,20 +6,23 @@ use std::fmt;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 use std::ops::{AddAssign, Mul, MulAssign};
-use std::rc::Rc;
 use std::slice::Iter;
+use std::sync::Arc;
 use std::vec::Vec;
 
 use integeriser::{HashIntegeriser, Integeriser};
-use recognisable::{self, Configuration, Instruction, Item, Recognisable, Transition};
+use recognisable::{self, Configuration, Instruction, Item, Recognisable, SearchOptions, Transition};
 use recognisable::automaton::Automaton;
+use recognisable::lattice::{Lattice, LatticeItem, LatticeRecognisable};
 use util::integerisable::{Integerisable1, Integerisable2};
 use util::push_down::Pushdown;
 
 mod from_cfg;
 // TODO: mod from_str;
+mod heuristic;
 
 pub use self::from_cfg::*;
+pub use self::heuristic::*;
 
 type TransitionMap<A, T, W>
     = HashMap<A, BinaryHeap<Transition<PushDownInstruction<A>, T, W>>>;
@@ -33,7 +36,7 @@ pub struct PushDownAutomaton<A, T, W>
 {
     a_integeriser: HashIntegeriser<A>,
     t_integeriser: HashIntegeriser<T>,
-    transitions: Rc<TransitionMap<usize, usize, W>>,
+    transitions: Arc<TransitionMap<usize, usize, W>>,
     initial: PushDown<usize>,
 }
 
@@ -149,7 +152,7 @@ impl<A, T, W> PushDownAutomaton<A, T, W>
         PushDownAutomaton {
             a_integeriser: a_inter,
             t_integeriser: t_inter,
-            transitions: Rc::new(transition_map.into_iter().map(f).collect()),
+            transitions: Arc::new(transition_map.into_iter().map(f).collect()),
             initial: init,
         }
     }
@@ -241,8 +244,32 @@ impl<A, T, W> Automaton<T, W> for PushDownAutomaton<A, T, W>
         }
     }
 
-    fn terminal_to_int(&self, t: &T) -> usize {
-        self.t_integeriser.find_key(t).unwrap()
+    fn terminal_to_int(&self, t: &T) -> Option<usize> {
+        self.t_integeriser.find_key(t)
+    }
+
+    fn int_to_terminal(&self, t: &usize) -> T {
+        self.t_integeriser.find_value(*t).unwrap().clone()
+    }
+
+    fn transition_to_int(&self, t: &Transition<PushDownInstruction<A>, T, W>)
+                         -> Option<Transition<PushDownInstruction<usize>, usize, W>>
+    {
+        let word = t.word.iter().map(|t| self.t_integeriser.find_key(t)).collect::<Option<Vec<_>>>()?;
+        let mut known = true;
+        let instruction = t.instruction.map_mut(&mut |a| match self.a_integeriser.find_key(a) {
+            Some(i) => i,
+            None => {
+                known = false;
+                0
+            },
+        });
+
+        if known {
+            Some(Transition { word, weight: t.weight.clone(), instruction })
+        } else {
+            None
+        }
     }
 
     fn extract_key(c: &Configuration<PushDown<usize>, usize, W>) -> &usize {
@@ -253,7 +280,7 @@ impl<A, T, W> Automaton<T, W> for PushDownAutomaton<A, T, W>
         c.word.is_empty() && c.storage.is_bottom()
     }
 
-    fn transition_map(&self) -> Rc<TransitionMap<usize, usize, W>> {
+    fn transition_map(&self) -> Arc<TransitionMap<usize, usize, W>> {
         self.transitions.clone()
     }
 
@@ -279,6 +306,37 @@ impl<A, T, W> Recognisable<T, W> for PushDownAutomaton<A, T, W>
     }
 }
 
+impl<A, T, W> PushDownAutomaton<A, T, W>
+    where A: Ord + PartialEq + Debug + Clone + Hash,
+          T: Clone + Debug + Eq + Hash + Ord,
+          W: AddAssign + One + Mul<Output=W> + MulAssign + Clone + Copy + Debug + Eq + Ord + Zero,
+{
+    /// Returns the `StackHeuristic` for the transitions of the automaton.
+    pub fn stack_heuristic(&self) -> StackHeuristic<W> {
+        StackHeuristic::new(self.transitions.values().flat_map(|h| h.iter()))
+    }
+
+    /// Like `recognise`, but the search is an A* search guided by the `StackHeuristic` of the
+    /// automaton.
+    pub fn recognise_astar<'a>(&'a self, word: Vec<T>, options: SearchOptions)
+                               -> Box<Iterator<Item=Item<PushDown<A>, PushDownInstruction<A>, T, W>> + 'a>
+    {
+        recognisable::automaton::recognise_astar(self, self.stack_heuristic(), word, options)
+    }
+}
+
+impl<A, T, W> LatticeRecognisable<T, W> for PushDownAutomaton<A, T, W>
+    where A: Ord + PartialEq + Debug + Clone + Hash,
+          T: Clone + Debug + Eq + Hash + Ord,
+          W: AddAssign + One + Mul<Output=W> + MulAssign + Clone + Copy + Debug + Eq + Ord + Zero,
+{
+    type Parse = LatticeItem<PushDown<A>, PushDownInstruction<A>, T, W>;
+
+    fn recognise_lattice<'a>(&'a self, lattice: Lattice<T, W>) -> Box<Iterator<Item=Self::Parse> + 'a> {
+        recognisable::lattice::recognise_lattice(self, lattice)
+    }
+}
+
 impl<A> PushDown<A> {
     pub fn empty(&self) -> &A {
         self.elements.get(0).unwrap()
@@ -1,12 +1,12 @@
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 use std::hash::Hash;
-use std::ops::MulAssign;
-use std::rc::Rc;
+use std::ops::{Mul, MulAssign};
+use std::sync::Arc;
 
 use num_traits::One;
 
-use recognisable::{Configuration, Instruction, Item, Recogniser, Transition};
-use util::agenda::{Agenda, BoundedPriorityQueue};
+use recognisable::{Configuration, GaveUp, Instruction, Item, Recogniser, SearchOptions, Transition};
+use util::agenda::{AStarAgenda, Agenda, BoundedPriorityQueue, Heuristic, KeyedBeamAgenda, ThresholdAgenda};
 use util::push_down::Pushdown;
 
 // map from key to transition
@@ -60,10 +60,20 @@ pub trait Automaton<T, W>
                 -> Item<<Self::I as Instruction>::Storage, Self::I, T, W>;
 
     /// Translates a terminal symbol to its internal representation.
+    /// Returns `None` if the terminal symbol does not occur in any transition.
     fn terminal_to_int(&self, t: &T)
-                       -> Self::TInt;
+                       -> Option<Self::TInt>;
 
 
+    /// Translates a terminal symbol from its internal representation.
+    fn int_to_terminal(&self, t: &Self::TInt)
+                       -> T;
+
+    /// Translates a `Transition` to its internal representation.
+    /// Returns `None` if one of its symbols does not occur in any transition of this `Automaton`.
+    fn transition_to_int(&self, t: &Transition<Self::I, T, W>)
+                         -> Option<Transition<Self::IInt, Self::TInt, W>>;
+
     /// Returns the `Self::Key` for the given `Configuration` (in its internal representation).
     fn extract_key(c: &Configuration<<Self::IInt as Instruction>::Storage, Self::TInt, W>)
                    -> &Self::Key;
@@ -75,69 +85,66 @@ pub trait Automaton<T, W>
 
     /// Returns a `Map` from `Self::Key` to the matching `Transition`s (in their internal representation).
     fn transition_map(&self)
-                      -> Rc<TransitionMap<Self::Key, Self::IInt, Self::TInt, W>>;
+                      -> Arc<TransitionMap<Self::Key, Self::IInt, Self::TInt, W>>;
 
     /// Returns the initial storage configuration (in its internal representation).
     fn initial_int(&self)
                    -> <Self::IInt as Instruction>::Storage;
 
-    // TODO documentation, implement function check_run_int(… Self::IInt …)
-    fn check_run(&self, run: Pushdown<Transition<Self::I, T, W>>)
-                 -> Vec<Item<<Self::I as Instruction>::Storage, Self::I, T, W>>
+    /// Checks the `run` (in the internal representation) transition by transition, starting
+    /// with the initial storage and the `word` that shall be read.
+    /// Returns the terminal `Configuration`s that are reached by the `run`, or the index of the
+    /// first transition that cannot be applied to any of the `Configuration`s reached before it
+    /// (e.g. because its instruction is not applicable or because it reads another symbol than
+    /// the `word`).
+    /// If the `run` can be applied, but does not end in a terminal `Configuration` (e.g. because
+    /// it does not read the whole `word`), the length of the `run` is returned.
+    fn check_run_int(&self, word: &[Self::TInt], run: &[Transition<Self::IInt, Self::TInt, W>])
+                     -> Result<Vec<Configuration<<Self::IInt as Instruction>::Storage, Self::TInt, W>>, usize>
+        where <Self::IInt as Instruction>::Storage: Clone,
+              Self::TInt: Clone + PartialEq,
+              W: Copy + Mul<Output=W>,
     {
-        let mut result = Vec::new();
-        let mut weight = W::one();
-        result.push(self.initial());
-        for t in run.iter() {
-            weight *= t.weight.clone();
-            let mut new_storages = Vec::new();
-            for s in result {
-                new_storages.append(&mut t.instruction.apply(s))
+        let mut configurations = vec![
+            Configuration {
+                word: word.to_vec(),
+                storage: self.initial_int(),
+                weight: W::one(),
+            }
+        ];
+        for (i, t) in run.iter().enumerate() {
+            let new_configurations = apply_to_configurations(t, &configurations);
+            if new_configurations.is_empty() {
+                return Err(i);
             }
-            result = new_storages;
+            configurations = new_configurations;
         }
-        result
-            .into_iter()
-            .map(|s| (Configuration { word: Vec::new(), storage: s, weight: weight.clone() }, run.clone()))
-            .collect()
-    }
 
-    /*
-    // TODO: remove
-    fn check_run(&self, run: &[Transition<Self::IInt, T, W>])
-                 -> Option<VecItem<<Self::IInt as Instruction>::Storage, Self::IInt, T, W>>
-    {
-        let heap = self.check(self.initial().clone(), run);
-        if heap.is_empty(){
-            return None;
+        configurations.retain(|c| Self::is_terminal(c));
+        if configurations.is_empty() {
+            Err(run.len())
+        } else {
+            Ok(configurations)
         }
-        let c = Configuration {
-            word: run_word(run),
-            storage: heap[0].clone(),
-            weight: run_weight(run),
-        };
-        Some((c, run.to_owned()))
     }
+}
 
-    // TODO: remove
-    // note: gives back the first configuration it finds
-    fn check<'b>(&'b self, storage: <Self::IInt as Instruction>::Storage, run: &[Transition<Self::IInt, T, W>])
-                 -> Vec<<Self::IInt as Instruction>::Storage>
-    {
-        let mut storage_heap = Vec::new();
-        storage_heap.push(storage);
-        for t in run {
-            let mut new_storage_heap = Vec::new();
-            for s in storage_heap{
-                for s1 in t.instruction.apply(s) {
-                    new_storage_heap.push(s1);
-                }
-            }
-            storage_heap = new_storage_heap;
-        }
-        storage_heap
+
+/// Applies the `transition` to each of the `configurations` and returns all `Configuration`s that
+/// are reached, i.e. one step of `Automaton::check_run_int`.
+pub fn apply_to_configurations<I, T, W>(transition: &Transition<I, T, W>,
+                                        configurations: &[Configuration<I::Storage, T, W>])
+                                        -> Vec<Configuration<I::Storage, T, W>>
+    where I: Instruction,
+          I::Storage: Clone,
+          T: Clone + PartialEq,
+          W: Copy + Mul<Output=W>,
+{
+    let mut result = Vec::new();
+    for c in configurations {
+        result.append(&mut transition.apply(c));
     }
-     */
+    result
 }
 
 
@@ -148,35 +155,256 @@ pub fn recognise<'a, A, T, W>(a: &'a A, word: Vec<T>)
           <A::I as Instruction>::Storage: Clone + Eq,
           A::IInt: 'a + Ord,
           A::Key: 'a,
-          <A::IInt as Instruction>::Storage: Clone + Eq + Ord,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
           T: Clone + Eq + Ord + 'a,
-          A::TInt: Clone + Eq + Ord,
-          W: Copy + MulAssign + One + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
 {
-    let i = Configuration {
-        word: word.iter().map(|t| a.terminal_to_int(t)).collect(),
-        storage: a.initial_int(),
-        weight: W::one(),
-    };
-
-    let mut init_heap = BinaryHeap::new();
-    init_heap.enqueue((i, Pushdown::new()));
-
-    Box::new(
-        Recogniser {
-            agenda: init_heap,
-            configuration_characteristic: Box::new(|c| A::extract_key(c)),
-            filtered_rules: a.transition_map(),
-            apply: Box::new(|c, r| r.apply(c)),
-            accepting: Box::new(|c| A::is_terminal(c)),
-            item_map: Box::new(move |i| a.item_map(&i)),
-        }
-    )
+    recognise_with_options(a, word, SearchOptions::default())
 }
 
 
 pub fn recognise_beam<'a, A, T, W>(a: &'a A, beam: usize, word: Vec<T>)
                                -> Box<Iterator<Item=Item<<A::I as Instruction>::Storage, A::I, T, W>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    recognise_beam_with_options(a, beam, word, SearchOptions::default())
+}
+
+
+/// Like `recognise`, but the search is configured by the given `SearchOptions`.
+pub fn recognise_with_options<'a, A, T, W>(a: &'a A, word: Vec<T>, options: SearchOptions)
+                                           -> Box<Iterator<Item=Item<<A::I as Instruction>::Storage, A::I, T, W>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    Box::new(recogniser_with_agenda(a, BinaryHeap::new(), word, options))
+}
+
+
+/// Like `recognise_beam`, but the search is configured by the given `SearchOptions`.
+pub fn recognise_beam_with_options<'a, A, T, W>(a: &'a A, beam: usize, word: Vec<T>, options: SearchOptions)
+                                                -> Box<Iterator<Item=Item<<A::I as Instruction>::Storage, A::I, T, W>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    Box::new(recogniser_with_agenda(a, BoundedPriorityQueue::new(beam), word, options))
+}
+
+
+/// Like `recognise_with_options`, but yields an `Err` if the search was aborted because one of
+/// the `SearchLimits` in `options` was exceeded.
+pub fn recognise_bounded<'a, A, T, W>(a: &'a A, word: Vec<T>, options: SearchOptions)
+                                      -> Box<Iterator<Item=Result<Item<<A::I as Instruction>::Storage, A::I, T, W>, GaveUp>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    Box::new(recogniser_with_agenda(a, BinaryHeap::new(), word, options).bounded())
+}
+
+
+/// Like `recognise_beam_with_options`, but yields an `Err` if the search was aborted because
+/// one of the `SearchLimits` in `options` was exceeded.
+pub fn recognise_beam_bounded<'a, A, T, W>(a: &'a A, beam: usize, word: Vec<T>, options: SearchOptions)
+                                           -> Box<Iterator<Item=Result<Item<<A::I as Instruction>::Storage, A::I, T, W>, GaveUp>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    Box::new(recogniser_with_agenda(a, BoundedPriorityQueue::new(beam), word, options).bounded())
+}
+
+
+/// Pruning policy of a beam search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BeamMode<W> {
+    /// keeps at most the given number of items in the agenda (`BoundedPriorityQueue`)
+    Width(usize),
+    /// drops items whose weight is less than the given factor times the weight of the best item
+    /// with the same length of the remaining word so far (`ThresholdAgenda`)
+    Threshold(W),
+    /// keeps at most the given number of items per length of the remaining word
+    /// (`KeyedBeamAgenda`)
+    Position(usize),
+    /// keeps at most the given number of items per `Automaton::Key` (`KeyedBeamAgenda`)
+    StorageKey(usize),
+}
+
+
+/// Like `recognise_beam_bounded`, but the beam is pruned according to the given `BeamMode`.
+pub fn recognise_beam_mode_bounded<'a, A, T, W>(a: &'a A, mode: BeamMode<W>, word: Vec<T>, options: SearchOptions)
+                                                -> Box<Iterator<Item=Result<Item<<A::I as Instruction>::Storage, A::I, T, W>, GaveUp>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a + Clone,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    match mode {
+        BeamMode::Width(beam) =>
+            Box::new(recogniser_with_agenda(a, BoundedPriorityQueue::new(beam), word, options).bounded()),
+        BeamMode::Threshold(threshold) => {
+            let agenda = ThresholdAgenda::new(
+                threshold,
+                Box::new(remaining_word_length::<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>)
+            );
+            Box::new(recogniser_with_agenda(a, agenda, word, options).bounded())
+        },
+        BeamMode::Position(beam) => {
+            let agenda = KeyedBeamAgenda::new(
+                beam,
+                Box::new(remaining_word_length::<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>)
+            );
+            Box::new(recogniser_with_agenda(a, agenda, word, options).bounded())
+        },
+        BeamMode::StorageKey(beam) => {
+            let agenda = KeyedBeamAgenda::new(beam, Box::new(storage_key::<A, T, W>));
+            Box::new(recogniser_with_agenda(a, agenda, word, options).bounded())
+        },
+    }
+}
+
+
+fn remaining_word_length<S, I, T, W>(item: &Item<S, I, T, W>) -> usize {
+    item.0.word.len()
+}
+
+
+fn storage_key<A, T, W>(item: &Item<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>) -> A::Key
+    where A: Automaton<T, W>,
+          A::I: Clone + Instruction,
+          A::IInt: Clone + Eq + Instruction,
+          A::Key: Clone,
+          T: Clone,
+          W: Clone + MulAssign + One,
+{
+    A::extract_key(&item.0).clone()
+}
+
+/// Like `recognise_with_options`, but the agenda is ordered by an `AStarAgenda` with the given
+/// `Heuristic` on the internal representation of the items.
+pub fn recognise_astar<'a, A, T, W, H>(a: &'a A, heuristic: H, word: Vec<T>, options: SearchOptions)
+                                       -> Box<Iterator<Item=Item<<A::I as Instruction>::Storage, A::I, T, W>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+          H: Heuristic<Item<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>, Weight=W> + 'a,
+{
+    Box::new(recogniser_with_agenda(a, AStarAgenda::new(heuristic), word, options))
+}
+
+
+/// Creates a `Recogniser` for the automaton `a` and the `word` that uses the given `agenda`.
+pub fn recogniser_with_agenda<'a, A, T, W, Ag>(a: &'a A, agenda: Ag, word: Vec<T>, options: SearchOptions)
+                                           -> Recogniser<'a,
+                                                         Ag,
+                                                         Configuration<<A::IInt as Instruction>::Storage, A::TInt, W>,
+                                                         Transition<A::IInt, A::TInt, W>,
+                                                         A::Key,
+                                                         Item<<A::I as Instruction>::Storage, A::I, T, W>>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+          Ag: Agenda<Item=Item<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>>,
+{
+    let mut agenda = agenda;
+    // a word with a terminal symbol that the automaton does not know is not recognised
+    if let Some(word) = word.iter().map(|t| a.terminal_to_int(t)).collect::<Option<Vec<_>>>() {
+        let i = Configuration {
+            word,
+            storage: a.initial_int(),
+            weight: W::one(),
+        };
+        agenda.enqueue((i, Pushdown::new()));
+    }
+
+    let closed_set = options.closed_set;
+    let mut recogniser = Recogniser::new(
+        agenda,
+        Box::new(|c| A::extract_key(c)),
+        a.transition_map(),
+        Box::new(|c, r| r.apply(c)),
+        Box::new(|c| A::is_terminal(c)),
+        Box::new(move |i| a.item_map(&i)),
+        options,
+    );
+    recogniser.closed_set = closed_set.map(closed_set_filter);
+    recogniser
+}
+
+
+/// Returns a predicate that holds for the first `k` `Configuration`s with the same storage and
+/// remaining word it is called with.
+fn closed_set_filter<'a, S, T, W>(k: usize) -> Box<FnMut(&Configuration<S, T, W>) -> bool + 'a>
+    where S: Clone + Ord + 'a,
+          T: Clone + Ord + 'a,
+          W: 'a,
+{
+    let mut expanded: BTreeMap<(S, Vec<T>), usize> = BTreeMap::new();
+    Box::new(move |c| {
+        let count = expanded.entry((c.storage.clone(), c.word.clone())).or_insert(0);
+        *count += 1;
+        *count <= k
+    })
+}
+
+
+/// Recognises the prefixes of the language of the automaton `a`, i.e. every `Configuration` in
+/// which the whole `word` has been read is accepting, regardless of `Automaton::is_terminal`.
+/// Note that a run may end in a configuration from which no terminal configuration is reachable.
+pub fn recognise_prefix<'a, A, T, W>(a: &'a A, word: Vec<T>)
+                                     -> Box<Iterator<Item=Item<<A::I as Instruction>::Storage, A::I, T, W>> + 'a>
     where A: Automaton<T, W>,
           A::I: Clone + Eq + Instruction,
           <A::I as Instruction>::Storage: Clone + Eq,
@@ -185,26 +413,90 @@ pub fn recognise_beam<'a, A, T, W>(a: &'a A, beam: usize, word: Vec<T>)
           <A::IInt as Instruction>::Storage: Clone + Eq + Ord,
           T: Clone + Eq + Ord + 'a,
           A::TInt: Clone + Eq + Ord,
-          W: Copy + MulAssign + One + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    let mut init_heap = BinaryHeap::new();
+    if let Some(word) = word.iter().map(|t| a.terminal_to_int(t)).collect::<Option<Vec<_>>>() {
+        let i = Configuration {
+            word,
+            storage: a.initial_int(),
+            weight: W::one(),
+        };
+        init_heap.enqueue((i, Pushdown::new()));
+    }
+
+    Box::new(
+        Recogniser::new(
+            init_heap,
+            Box::new(|c| A::extract_key(c)),
+            a.transition_map(),
+            Box::new(|c, r| r.apply(c)),
+            Box::new(|c| c.word.is_empty()),
+            Box::new(move |i| a.item_map(&i)),
+            SearchOptions::default(),
+        )
+    )
+}
+
+
+/// Returns the terminal symbols that may be read by the automaton `a` after it has read the
+/// `prefix`, each with the weight of the best (partial) run that reads it.
+/// The result is sorted by descending weight.
+/// The search is best-first and explores at most `max_items` configurations, since there may be
+/// infinitely many configurations in which the `prefix` has been read.
+pub fn next_terminals<A, T, W>(a: &A, prefix: Vec<T>, max_items: usize) -> Vec<(T, W)>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          A::IInt: Ord,
+          A::Key: Eq + Hash,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord,
+          T: Clone + Eq + Ord,
+          A::TInt: Clone + Eq + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
 {
-    let i = Configuration {
-        word: word.iter().map(|t| a.terminal_to_int(t)).collect(),
+    let word = match prefix.iter().map(|t| a.terminal_to_int(t)).collect::<Option<Vec<_>>>() {
+        Some(word) => word,
+        None => return Vec::new(),
+    };
+    let transition_map = a.transition_map();
+    let mut agenda = BinaryHeap::new();
+    agenda.push(Configuration {
+        word,
         storage: a.initial_int(),
         weight: W::one(),
-    };
+    });
 
-    let mut init_heap = BoundedPriorityQueue::new(beam);
-    init_heap.enqueue((i, Pushdown::new()));
+    let mut predictions: BTreeMap<A::TInt, W> = BTreeMap::new();
+    let mut explored = 0;
+    while let Some(c) = agenda.pop() {
+        if explored == max_items {
+            break;
+        }
+        explored += 1;
 
-    Box::new(
-        Recogniser {
-            agenda: init_heap,
-            configuration_characteristic: Box::new(|c| A::extract_key(c)),
-            filtered_rules: a.transition_map(),
-            apply: Box::new(|c, r| r.apply(c)),
-            accepting: Box::new(|c| A::is_terminal(c)),
-            item_map: Box::new(move |i| a.item_map(&i)),
+        if let Some(rs) = transition_map.get(A::extract_key(&c)) {
+            for r in rs {
+                if c.word.is_empty() && !r.word.is_empty() {
+                    if !r.instruction.apply(c.storage.clone()).is_empty() {
+                        let weight = c.weight * r.weight;
+                        let best = predictions.entry(r.word[0].clone()).or_insert(weight);
+                        if *best < weight {
+                            *best = weight;
+                        }
+                    }
+                } else {
+                    for c1 in r.apply(&c) {
+                        agenda.push(c1);
+                    }
+                }
+            }
         }
-    )
-}
+    }
 
+    let mut result: Vec<_> = predictions
+        .into_iter()
+        .map(|(t, w)| (a.int_to_terminal(&t), w))
+        .collect();
+    result.sort_by(|&(_, ref w1), &(_, ref w2)| w2.cmp(w1));
+    result
+}
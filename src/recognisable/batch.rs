@@ -0,0 +1,110 @@
+use std::cmp::max;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+
+/// Applies `parse` to every sentence of the `corpus` on `threads` worker threads that share the
+/// `recogniser`, and returns an iterator over the results in the order of the `corpus`.
+/// Each result is yielded as soon as the results of all preceding sentences have been yielded,
+/// i.e. only the results that are finished ahead of their turn are buffered.
+///
+/// The results of `parse` are sent between threads, so they should not contain `Rc`s, e.g.
+/// parses should be converted into `String`s.
+pub fn parse_batch<R, S, O, F>(recogniser: Arc<R>, corpus: Vec<S>, threads: usize, parse: F) -> BatchResults<O>
+    where R: Send + Sync + 'static,
+          S: Send + 'static,
+          O: Send + 'static,
+          F: Fn(&R, S) -> O + Send + Sync + 'static,
+{
+    let sentences = Arc::new(Mutex::new(corpus.into_iter().enumerate()));
+    let parse = Arc::new(parse);
+    let (sender, receiver) = mpsc::channel();
+
+    let mut workers = Vec::new();
+    for _ in 0..max(threads, 1) {
+        let recogniser = recogniser.clone();
+        let sentences = sentences.clone();
+        let parse = parse.clone();
+        let sender = sender.clone();
+        workers.push(thread::spawn(move || loop {
+            let next = sentences.lock().unwrap().next();
+            match next {
+                Some((i, sentence)) => sender.send((i, (*parse)(&*recogniser, sentence))).unwrap(),
+                None => break,
+            }
+        }));
+    }
+
+    BatchResults {
+        receiver,
+        pending: BTreeMap::new(),
+        next: 0,
+        workers,
+    }
+}
+
+/// Iterator over the results of `parse_batch` in the order of the corpus.
+pub struct BatchResults<O> {
+    receiver: mpsc::Receiver<(usize, O)>,
+    /// results that were finished before the results of all preceding sentences
+    pending: BTreeMap<usize, O>,
+    next: usize,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl<O> Iterator for BatchResults<O> {
+    type Item = O;
+
+    fn next(&mut self) -> Option<O> {
+        loop {
+            if let Some(output) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Some(output);
+            }
+            match self.receiver.recv() {
+                Ok((i, output)) => {
+                    self.pending.insert(i, output);
+                },
+                Err(_) => {
+                    // every worker has finished (or panicked)
+                    for worker in self.workers.drain(..) {
+                        worker.join().unwrap();
+                    }
+                    return None;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch() {
+        let corpus: Vec<Vec<usize>> = (0..20).map(|n| (0..n).collect()).collect();
+        let offset = Arc::new(100usize);
+        let results: Vec<_> = parse_batch(offset, corpus, 4, |offset, sentence| *offset + sentence.len()).collect();
+        assert_eq!((100..120).collect::<Vec<_>>(), results);
+    }
+
+    #[test]
+    fn test_parse_batch_streaming() {
+        // the second sentence is only finished after the result of the first one was yielded
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Mutex::new(receiver);
+        let mut results = parse_batch(Arc::new(()), vec![0, 1], 2, move |_, i| {
+            if i == 1 {
+                receiver.lock().unwrap().recv().unwrap();
+            }
+            i
+        });
+
+        assert_eq!(Some(0), results.next());
+        sender.send(()).unwrap();
+        assert_eq!(Some(1), results.next());
+        assert_eq!(None, results.next());
+    }
+}
@@ -0,0 +1,327 @@
+use num_traits::{One, Zero};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::ops::{AddAssign, Div, Mul, MulAssign};
+use std::sync::Arc;
+
+use approximation::{ApproximationInstance, ApproximationStrategy};
+use recognisable::{Configuration, Instruction, Item, Recognisable, Recogniser, SearchOptions, Transition};
+use recognisable::automaton::Automaton;
+use util::agenda::{Agenda, BoundedPriorityQueue};
+use util::push_down::Pushdown;
+
+/// The items of a chart: the `Transition`s that may be applied after reading the given number
+/// of terminal symbols.
+pub type Chart<I, T, W> = BTreeMap<usize, BTreeSet<Transition<I, T, W>>>;
+
+/// A coarse-to-fine recogniser that, instead of checking every fine run of every coarse parse
+/// (cf. `coarse_to_fine::CoarseToFineRecogniser`), prunes the search space of the fine
+/// automaton before it is searched.
+///
+/// A chart item is a `Transition` together with the number of terminal symbols that have been
+/// read before it is applied.
+/// The posterior of a coarse item is the summed weight of the accepting runs of the coarse
+/// automaton (the `sublevel`) that apply it, divided by the summed weight of all accepting runs.
+/// It is computed from the inside and outside weights of the coarse configurations that are
+/// reachable while reading the word; at most `max_coarse_items` of them are explored.
+/// A fine item is kept if the `ApproximationStrategy` projects it to a coarse item whose
+/// posterior is at least the `threshold`; the fine automaton then only applies kept items.
+/// A parse of the fine automaton may be lost if the exploration of the coarse configurations is
+/// cut off, or if a coarse item of its projection has a posterior below the `threshold`.
+pub struct ChartPruningRecogniser<Rec, SubRec, Strategy, T, W>
+    where Rec: Automaton<T, W>,
+          SubRec: Automaton<T, W>,
+          Strategy: ApproximationStrategy<T, W>,
+          Strategy::I1: Instruction,
+          Strategy::I2: Instruction,
+          T: Clone + Eq + Ord,
+          W: Clone + MulAssign + One + Ord,
+{
+    pub threshold: W,
+    pub max_coarse_items: usize,
+    recogniser: Arc<Rec>,
+    sublevel: Arc<SubRec>,
+    approximation_instance: Arc<ApproximationInstance<Strategy, T, W>>,
+}
+
+impl<Rec, SubRec, Strategy, T, W> ChartPruningRecogniser<Rec, SubRec, Strategy, T, W>
+    where Rec: Automaton<T, W, I=Strategy::I1>,
+          Rec::TInt: Clone,
+          SubRec: Automaton<T, W, I=Strategy::I2>,
+          SubRec::IInt: Ord,
+          <SubRec::IInt as Instruction>::Storage: Clone + Ord,
+          SubRec::TInt: Clone + Ord,
+          Strategy: ApproximationStrategy<T, W>,
+          Strategy::I1: Clone + Eq + Ord,
+          Strategy::I2: Clone + Eq + Ord,
+          T: Clone + Eq + Ord,
+          W: AddAssign + Copy + Div<Output=W> + Mul<Output=W> + MulAssign + One + Ord + Zero,
+{
+    /// Creates a `ChartPruningRecogniser` for the `automaton` whose coarse automaton is the
+    /// `sublevel`, e.g. the automaton that was approximated by the `approximation_instance`.
+    pub fn new(automaton: Rec,
+               sublevel: SubRec,
+               approximation_instance: ApproximationInstance<Strategy, T, W>,
+               threshold: W,
+               max_coarse_items: usize)
+               -> Self
+    {
+        ChartPruningRecogniser {
+            threshold,
+            max_coarse_items,
+            recogniser: Arc::new(automaton),
+            sublevel: Arc::new(sublevel),
+            approximation_instance: Arc::new(approximation_instance),
+        }
+    }
+
+    /// Returns the posteriors of the coarse items of the `word` (cf. `ChartPruningRecogniser`).
+    pub fn coarse_posteriors(&self, word: Vec<T>) -> BTreeMap<(usize, Transition<Strategy::I2, T, W>), W> {
+        let automaton = &*self.sublevel;
+        let mut posteriors = BTreeMap::new();
+        let word = match word.iter().map(|t| automaton.terminal_to_int(t)).collect::<Option<Vec<_>>>() {
+            Some(word) => word,
+            None => return posteriors,
+        };
+
+        let chart = CoarseChart::explore(automaton, &word, self.max_coarse_items);
+        let (inside, outside) = chart.inside_outside();
+        let total = match inside.first() {
+            Some(&total) if total > W::zero() => total,
+            _ => return posteriors,
+        };
+
+        let mut masses = BTreeMap::new();
+        for &(source, target, ref transition) in &chart.transitions {
+            let mass = outside[source] * transition.weight * inside[target];
+            *masses.entry((chart.configurations[source].1, transition.clone())).or_insert_with(W::zero) += mass;
+        }
+
+        let initial = Configuration {
+            word: Vec::new(),
+            storage: automaton.initial_int(),
+            weight: W::one(),
+        };
+        for ((position, transition), mass) in masses {
+            let (_, run) = automaton.item_map(&(initial.clone(), Pushdown::new().push(transition)));
+            if let Some(transition) = run.peek() {
+                posteriors.insert((position, transition), mass / total);
+            }
+        }
+        posteriors
+    }
+
+    /// Returns the fine items of the `word` that survive the pruning.
+    pub fn chart(&self, word: Vec<T>) -> Chart<Strategy::I1, T, W> {
+        let mut chart = BTreeMap::new();
+        for ((position, coarse_transition), posterior) in self.coarse_posteriors(word) {
+            if posterior < self.threshold {
+                continue;
+            }
+            for fine_transition in self.approximation_instance.unapproximate_transition(&coarse_transition) {
+                chart.entry(position).or_insert_with(BTreeSet::new).insert(fine_transition);
+            }
+        }
+        chart
+    }
+
+    fn recognise_with_agenda<'a, Ag>(&'a self, agenda: Ag, word: Vec<T>)
+                                     -> Box<Iterator<Item=Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>> + 'a>
+        where Ag: Agenda<Item=Item<<Rec::IInt as Instruction>::Storage, Rec::IInt, Rec::TInt, W>> + 'a,
+              Rec::IInt: Ord,
+              <Rec::IInt as Instruction>::Storage: Clone + Ord,
+              Rec::TInt: Ord,
+    {
+        let automaton = &*self.recogniser;
+        let mut chart_int: Chart<Rec::IInt, Rec::TInt, W> = BTreeMap::new();
+        for (position, transitions) in self.chart(word.clone()) {
+            chart_int.insert(
+                position,
+                transitions.iter().filter_map(|t| automaton.transition_to_int(t)).collect()
+            );
+        }
+
+        let mut agenda = agenda;
+        let length = word.len();
+        if !chart_int.is_empty() {
+            if let Some(word) = word.iter().map(|t| automaton.terminal_to_int(t)).collect::<Option<Vec<_>>>() {
+                let i = Configuration {
+                    word,
+                    storage: automaton.initial_int(),
+                    weight: W::one(),
+                };
+                agenda.enqueue((i, Pushdown::new()));
+            }
+        }
+
+        Box::new(
+            Recogniser::new(
+                agenda,
+                Box::new(|c| Rec::extract_key(c)),
+                automaton.transition_map(),
+                Box::new(move |c, r| {
+                    let kept = chart_int
+                        .get(&(length - c.word.len()))
+                        .map_or(false, |transitions| transitions.contains(r));
+                    if kept {
+                        r.apply(c)
+                    } else {
+                        Vec::new()
+                    }
+                }),
+                Box::new(|c| Rec::is_terminal(c)),
+                Box::new(move |i| automaton.item_map(&i)),
+                SearchOptions::default(),
+            )
+        )
+    }
+}
+
+impl<Rec, SubRec, Strategy, T, W> Recognisable<T, W> for ChartPruningRecogniser<Rec, SubRec, Strategy, T, W>
+    where Rec: Automaton<T, W, I=Strategy::I1>,
+          Rec::IInt: Ord,
+          <Rec::IInt as Instruction>::Storage: Clone + Ord,
+          Rec::TInt: Clone + Ord,
+          SubRec: Automaton<T, W, I=Strategy::I2>,
+          SubRec::IInt: Ord,
+          <SubRec::IInt as Instruction>::Storage: Clone + Ord,
+          SubRec::TInt: Clone + Ord,
+          Strategy: ApproximationStrategy<T, W>,
+          Strategy::I1: Clone + Eq + Ord,
+          Strategy::I2: Clone + Eq + Ord,
+          T: Clone + Eq + Ord,
+          W: AddAssign + Copy + Div<Output=W> + Mul<Output=W> + MulAssign + One + Ord + Zero,
+{
+    type Parse = Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>;
+
+    fn recognise<'a>(&'a self, word: Vec<T>) -> Box<Iterator<Item=Self::Parse> + 'a> {
+        self.recognise_with_agenda(BinaryHeap::new(), word)
+    }
+
+    fn recognise_beam_search<'a>(&'a self, beam: usize, word: Vec<T>) -> Box<Iterator<Item=Self::Parse> + 'a> {
+        self.recognise_with_agenda(BoundedPriorityQueue::new(beam), word)
+    }
+}
+
+/// The configurations of an automaton that are reachable from its initial configuration while
+/// reading a word, and the transitions between them.
+struct CoarseChart<S, I, T, W> {
+    /// the storage of each configuration and the number of terminal symbols read before it
+    configurations: Vec<(S, usize)>,
+    terminal: Vec<bool>,
+    /// the source, the target, and the applied transition
+    transitions: Vec<(usize, usize, Transition<I, T, W>)>,
+}
+
+impl<S, I, T, W> CoarseChart<S, I, T, W>
+    where S: Clone + Ord,
+          I: Clone + Instruction<Storage=S>,
+          T: Clone + PartialEq,
+          W: AddAssign + Copy + Mul<Output=W> + One + PartialEq + Zero,
+{
+    /// Explores the configurations of the `automaton` (in its internal representation) in
+    /// breadth-first order, starting with its initial configuration; stops adding
+    /// configurations when there are `max_configurations`.
+    fn explore<A, T1>(automaton: &A, word: &[T], max_configurations: usize) -> Self
+        where A: Automaton<T1, W, IInt=I, TInt=T>,
+              T1: Clone,
+              W: Clone + MulAssign,
+    {
+        let transition_map = automaton.transition_map();
+        let mut index = BTreeMap::new();
+        let mut chart = CoarseChart {
+            configurations: vec![(automaton.initial_int(), 0)],
+            terminal: Vec::new(),
+            transitions: Vec::new(),
+        };
+        index.insert((automaton.initial_int(), 0), 0);
+
+        let mut next = 0;
+        while next < chart.configurations.len() {
+            let (storage, position) = chart.configurations[next].clone();
+            let configuration = Configuration {
+                word: word[position..].to_vec(),
+                storage,
+                weight: W::one(),
+            };
+            chart.terminal.push(A::is_terminal(&configuration));
+
+            if let Some(transitions) = transition_map.get(A::extract_key(&configuration)) {
+                for transition in transitions.iter() {
+                    for successor in transition.apply(&configuration) {
+                        let key = (successor.storage, word.len() - successor.word.len());
+                        let known = index.get(&key).cloned();
+                        let target = match known {
+                            Some(target) => target,
+                            None if chart.configurations.len() < max_configurations => {
+                                index.insert(key.clone(), chart.configurations.len());
+                                chart.configurations.push(key);
+                                chart.configurations.len() - 1
+                            },
+                            None => continue,
+                        };
+                        chart.transitions.push((next, target, transition.clone()));
+                    }
+                }
+            }
+            next += 1;
+        }
+
+        chart
+    }
+
+    /// Returns the inside weight of each configuration, i.e. the summed weight of the runs from
+    /// it to a terminal configuration, and its outside weight, i.e. the summed weight of the runs
+    /// from the initial configuration to it.
+    /// The weights are updated until they do not change (which takes few rounds, since the
+    /// configurations are numbered in breadth-first order); if the chart contains cycles, the
+    /// number of rounds is bounded by the number of configurations.
+    fn inside_outside(&self) -> (Vec<W>, Vec<W>) {
+        let n = self.configurations.len();
+        let mut outgoing = vec![Vec::new(); n];
+        let mut incoming = vec![Vec::new(); n];
+        for (e, &(source, target, _)) in self.transitions.iter().enumerate() {
+            outgoing[source].push(e);
+            incoming[target].push(e);
+        }
+
+        let mut inside = vec![W::zero(); n];
+        for _ in 0..n {
+            let mut changed = false;
+            for i in (0..n).rev() {
+                let mut weight = if self.terminal[i] { W::one() } else { W::zero() };
+                for &e in &outgoing[i] {
+                    let (_, target, ref transition) = self.transitions[e];
+                    weight += transition.weight * inside[target];
+                }
+                if weight != inside[i] {
+                    inside[i] = weight;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut outside = vec![W::zero(); n];
+        for _ in 0..n {
+            let mut changed = false;
+            for i in 0..n {
+                let mut weight = if i == 0 { W::one() } else { W::zero() };
+                for &e in &incoming[i] {
+                    let (source, _, ref transition) = self.transitions[e];
+                    weight += outside[source] * transition.weight;
+                }
+                if weight != outside[i] {
+                    outside[i] = weight;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        (inside, outside)
+    }
+}
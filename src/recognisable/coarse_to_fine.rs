@@ -1,9 +1,14 @@
-use num_traits::One;
+use num_traits::{One, Zero};
 use std::collections::BinaryHeap;
-use std::ops::MulAssign;
-use std::rc::Rc;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::iter;
+use std::ops::{AddAssign, Mul, MulAssign};
+use std::sync::Arc;
 
-use approximation::{ApproximationInstance, ApproximationStrategy};
+use approximation::{ApproximationInstance, ApproximationStrategy, FineRuns};
+use approximation::relabel::RlbElement;
+use push_down_automaton::{PushDown, PushDownAutomaton, PushDownInstruction};
 use recognisable::{Instruction, Item, Recognisable};
 use recognisable::automaton::Automaton;
 use util::agenda::Weighted;
@@ -17,9 +22,9 @@ pub struct CoarseToFineRecogniser<Rec, SubRec, Strategy, T, W>
           T: Clone + Eq + Ord,
           W: Clone + MulAssign + One + Ord,
 {
-    pub recogniser: Rc<Rec>,
-    pub sublevel: Rc<SubRec>,
-    pub approximation_instance: Rc<ApproximationInstance<Strategy, T, W>>,
+    pub recogniser: Arc<Rec>,
+    pub sublevel: Arc<SubRec>,
+    pub approximation_instance: Arc<ApproximationInstance<Strategy, T, W>>,
 }
 
 struct CoarseToFineParseForest<'a, Rec, Strategy, T, W>
@@ -30,10 +35,15 @@ struct CoarseToFineParseForest<'a, Rec, Strategy, T, W>
           W: 'a + Clone + MulAssign + One + Ord,
 {
     sublevel_parses: Box<Iterator<Item=Item<<Strategy::I2 as Instruction>::Storage, Strategy::I2, T, W>> + 'a>,
-    recogniser: Rc<Rec>,
-    approximation_instance: Rc<ApproximationInstance<Strategy, T, W>>,
+    recogniser: Arc<Rec>,
+    approximation_instance: Arc<ApproximationInstance<Strategy, T, W>>,
     input_buffer: Option<Option<Item<<Strategy::I2 as Instruction>::Storage, Strategy::I2, T, W>>>,
-    output_buffer: BinaryHeap<Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>>,
+    /// the best fine parse of each of the `fine_runs` that is not yet returned, together with the
+    /// index of the respective `FineRuns`
+    output_buffer: BinaryHeap<(Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>, usize)>,
+    fine_runs: Vec<FineRuns<Rec::IInt, Rec::TInt, W>>,
+    /// the input word in the internal representation of the `recogniser`
+    word: Vec<Rec::TInt>,
 }
 
 impl<'a, Rec, Strategy, T, W> CoarseToFineParseForest<'a, Rec, Strategy, T, W>
@@ -55,58 +65,90 @@ impl<'a, Rec, Strategy, T, W> CoarseToFineParseForest<'a, Rec, Strategy, T, W>
     }
 
     fn next_input(&mut self) -> Option<Item<<Strategy::I2 as Instruction>::Storage, Strategy::I2, T, W>> {
-        if self.input_buffer.is_none() {
-            self.input_buffer = Some(self.sublevel_parses.next());
-        }
-        match self.input_buffer {
-            Some(ref mut x) => x.take(),
-            _ => unreachable!(),
+        // the buffer is emptied, such that the following coarse parse is requested next time
+        match self.input_buffer.take() {
+            Some(x) => x,
+            None => self.sublevel_parses.next(),
         }
     }
 }
 
+impl<'a, Rec, Strategy, T, W> CoarseToFineParseForest<'a, Rec, Strategy, T, W>
+    where Rec: Automaton<T, W, I=Strategy::I1>,
+          Rec::IInt: Ord,
+          <Rec::IInt as Instruction>::Storage: Clone + Ord,
+          Rec::TInt: Clone + Ord,
+          Strategy: ApproximationStrategy<T, W>,
+          Strategy::I1: Instruction + Ord,
+          <Strategy::I1 as Instruction>::Storage: Clone + Ord,
+          T: Clone + Eq + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
+{
+    /// Returns the next fine parse of the `k`th `FineRuns`.
+    fn next_fine_parse(&mut self, k: usize) -> Option<Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>> {
+        let recogniser = &self.recogniser;
+        self.fine_runs[k].next().map(|i1| recogniser.item_map(&i1))
+    }
+}
+
 impl<'a, Rec, Strategy, T, W> Iterator for CoarseToFineParseForest<'a, Rec, Strategy, T, W>
     where Rec: Automaton<T, W, I=Strategy::I1>,
+          Rec::IInt: Ord,
+          <Rec::IInt as Instruction>::Storage: Clone + Ord,
+          Rec::TInt: Clone + Ord,
           Strategy: ApproximationStrategy<T, W>,
           Strategy::I1: Instruction + Ord,
-          <Strategy::I1 as Instruction>::Storage: Ord,
+          <Strategy::I1 as Instruction>::Storage: Clone + Ord,
           T: Clone + Eq + Ord,
-          W: Clone + MulAssign + One + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
 {
     type Item = Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>;
 
     fn next(&mut self) -> Option<Self::Item> {
         while self.output_buffer.is_empty()
             || self.peek_input().is_some()
-            && self.output_buffer.peek().unwrap().get_weight() < self.peek_input().unwrap().get_weight()
+            && self.output_buffer.peek().unwrap().0.get_weight() < self.peek_input().unwrap().get_weight()
         {
             if let Some((_, r2)) = self.next_input() {
-                let r1s = self.approximation_instance.unapproximate_run(r2);
-                for r1 in r1s {
-                    for i1 in self.recogniser.check_run(r1) {
-                        self.output_buffer.push(i1);
-                    }
+                let r1s = self.approximation_instance.unapproximate_run_lazily(r2, &*self.recogniser, self.word.clone());
+                self.fine_runs.push(r1s);
+                let k = self.fine_runs.len() - 1;
+                if let Some(i1) = self.next_fine_parse(k) {
+                    self.output_buffer.push((i1, k));
                 }
             } else {
                 return None;
             }
         }
-        self.output_buffer.pop()
+
+        let (i1, k) = self.output_buffer.pop().unwrap();
+        if let Some(next_i1) = self.next_fine_parse(k) {
+            self.output_buffer.push((next_i1, k));
+        }
+        Some(i1)
     }
 }
 
 impl<Rec, SubRec, Strategy, T, W> Recognisable<T, W> for CoarseToFineRecogniser<Rec, SubRec, Strategy, T, W>
     where Rec: Automaton<T, W, I=Strategy::I1>,
+          Rec::IInt: Ord,
+          <Rec::IInt as Instruction>::Storage: Clone + Ord,
+          Rec::TInt: Clone + Ord,
           SubRec: Recognisable<T, W, Parse=Item<<Strategy::I2 as Instruction>::Storage, Strategy::I2, T, W>>,
           Strategy: ApproximationStrategy<T, W>,
           Strategy::I1: Instruction + Ord,
-          <Strategy::I1 as Instruction>::Storage: Ord,
+          <Strategy::I1 as Instruction>::Storage: Clone + Ord,
           T: Clone + Eq + Ord,
-          W: Clone + MulAssign + One + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
 {
     type Parse = Item<<Strategy::I1 as Instruction>::Storage, Strategy::I1, T, W>;
 
     fn recognise<'a>(&'a self, word: Vec<T>) -> Box<Iterator<Item=Self::Parse> + 'a> {
+        // a word with an unknown terminal symbol has no fine parse
+        let word_int = match word.iter().map(|t| self.recogniser.terminal_to_int(t)).collect::<Option<Vec<_>>>() {
+            Some(word_int) => word_int,
+            None => return Box::new(iter::empty()),
+        };
         Box::new(
             CoarseToFineParseForest {
                 sublevel_parses: self.sublevel.recognise(word),
@@ -114,6 +156,8 @@ impl<Rec, SubRec, Strategy, T, W> Recognisable<T, W> for CoarseToFineRecogniser<
                 approximation_instance: self.approximation_instance.clone(),
                 input_buffer: None,
                 output_buffer: BinaryHeap::new(),
+                fine_runs: Vec::new(),
+                word: word_int,
             }
         )
     }
@@ -123,15 +167,55 @@ impl<Rec, SubRec, Strategy, T, W> Recognisable<T, W> for CoarseToFineRecogniser<
     }
 }
 
+impl<'b, T, W, P> Recognisable<T, W> for Box<Recognisable<T, W, Parse=P> + 'b> {
+    type Parse = P;
+
+    fn recognise<'a>(&'a self, word: Vec<T>) -> Box<Iterator<Item=P> + 'a> {
+        (**self).recognise(word)
+    }
+
+    fn recognise_beam_search<'a>(&'a self, beam: usize, word: Vec<T>) -> Box<Iterator<Item=P> + 'a> {
+        (**self).recognise_beam_search(beam, word)
+    }
+}
+
+/// Builds a coarse-to-fine recogniser for the `automaton` with one `RlbElement` level for each
+/// of the `mappings`, which are applied one after the other (i.e. from the finest to the
+/// coarsest level), e.g. the mappings induced by the `relabellings` of a
+/// `approximation::equivalence_classes::RelationHierarchy`.
+/// In contrast to `coarse_to_fine_recogniser!`, the number of levels need not be known at
+/// compile time.
+pub fn relabelling_recogniser<'a, A, T, W>(automaton: PushDownAutomaton<A, T, W>,
+                                           mappings: &'a [Box<Fn(&A) -> A>])
+                                           -> Box<Recognisable<T, W, Parse=Item<PushDown<A>, PushDownInstruction<A>, T, W>> + 'a>
+    where A: Clone + Debug + Hash + Ord + 'a,
+          T: Clone + Debug + Eq + Hash + Ord + 'a,
+          W: AddAssign + Copy + Debug + Mul<Output=W> + MulAssign + One + Ord + Zero + 'a,
+{
+    match mappings.split_first() {
+        None => Box::new(automaton),
+        Some((mapping, coarser_mappings)) => {
+            let (coarse_automaton, instance) = RlbElement::new(&**mapping).approximate_automaton(&automaton);
+            Box::new(
+                CoarseToFineRecogniser {
+                    recogniser: Arc::new(automaton),
+                    sublevel: Arc::new(relabelling_recogniser(coarse_automaton, coarser_mappings)),
+                    approximation_instance: Arc::new(instance),
+                }
+            )
+        },
+    }
+}
+
 #[macro_export]
 macro_rules! coarse_to_fine_recogniser {
     ( $automaton:expr; $strategy:expr ) => {
         {
             let (aut0, strat_instance) = $strategy.approximate_automaton(&$automaton);
             CoarseToFineRecogniser {
-                sublevel: Rc::new(aut0),
-                recogniser: Rc::new($automaton),
-                approximation_instance: Rc::new(strat_instance),
+                sublevel: Arc::new(aut0),
+                recogniser: Arc::new($automaton),
+                approximation_instance: Arc::new(strat_instance),
             }
         }
     };
@@ -139,9 +223,9 @@ macro_rules! coarse_to_fine_recogniser {
         {
             let (aut0, strat_instance) = $strategy.approximate_automaton(&$automaton);
             CoarseToFineRecogniser {
-                sublevel: Rc::new(coarse_to_fine_recogniser!(aut0; $( $strats ),*)),
-                recogniser: Rc::new($automaton),
-                approximation_instance: Rc::new(strat_instance),
+                sublevel: Arc::new(coarse_to_fine_recogniser!(aut0; $( $strats ),*)),
+                recogniser: Arc::new($automaton),
+                approximation_instance: Arc::new(strat_instance),
             }
         }
     }
@@ -0,0 +1,174 @@
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+use std::ops::{Mul, MulAssign};
+use std::sync::Arc;
+
+use num_traits::One;
+
+use recognisable::{Configuration, Instruction, Item, Transition};
+use recognisable::automaton::{Automaton, TransitionMap};
+use recognisable::lattice::{LatticeConfiguration, LatticeItem};
+use util::push_down::Pushdown;
+
+type IncrementalItem<A, T, W>
+    = LatticeItem<<<A as Automaton<T, W>>::IInt as Instruction>::Storage,
+                  <A as Automaton<T, W>>::IInt,
+                  <A as Automaton<T, W>>::TInt,
+                  W>;
+
+/// Recogniser that receives its input one token at a time.
+///
+/// The agenda is kept between two calls of `push_token`: items that would need to read tokens
+/// that have not been pushed yet are put aside and resumed once these tokens arrive.
+/// The `node` of each `LatticeConfiguration` is the number of tokens that were read.
+///
+/// The parses of the current input are enumerated best-first, assuming that the weights of the
+/// automaton do not increase along a run (e.g. probabilities).
+pub struct IncrementalRecogniser<'a, A, T, W>
+    where A: Automaton<T, W> + 'a,
+          A::I: Clone + Instruction,
+          A::IInt: Clone + Eq + Instruction,
+          T: Clone,
+          W: Clone + MulAssign + One,
+{
+    automaton: &'a A,
+    transition_map: Arc<TransitionMap<A::Key, A::IInt, A::TInt, W>>,
+    word: Vec<A::TInt>,
+    /// whether a token was pushed that the automaton does not know
+    unknown_token: bool,
+    agenda: BinaryHeap<IncrementalItem<A, T, W>>,
+    blocked: Vec<(IncrementalItem<A, T, W>, Transition<A::IInt, A::TInt, W>)>,
+    accepted: Vec<IncrementalItem<A, T, W>>,
+}
+
+impl<'a, A, T, W> IncrementalRecogniser<'a, A, T, W>
+    where A: Automaton<T, W> + 'a,
+          A::Key: Eq + Hash,
+          A::I: Clone + Eq + Instruction,
+          A::IInt: Clone + Eq + Instruction + Ord,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord,
+          T: Clone + Eq + Ord,
+          A::TInt: Clone + Eq + Ord,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord,
+{
+    /// Creates an `IncrementalRecogniser` for the automaton `a` that has not read any token yet.
+    pub fn new(a: &'a A) -> Self {
+        let mut recogniser = IncrementalRecogniser {
+            automaton: a,
+            transition_map: a.transition_map(),
+            word: Vec::new(),
+            unknown_token: false,
+            agenda: BinaryHeap::new(),
+            blocked: Vec::new(),
+            accepted: Vec::new(),
+        };
+        recogniser.reset();
+        recogniser
+    }
+
+    /// Forgets all tokens that were pushed so far.
+    pub fn reset(&mut self) {
+        self.word.clear();
+        self.unknown_token = false;
+        self.agenda.clear();
+        self.blocked.clear();
+        self.accepted.clear();
+
+        let i = LatticeConfiguration {
+            node: 0,
+            configuration: Configuration {
+                word: Vec::new(),
+                storage: self.automaton.initial_int(),
+                weight: W::one(),
+            },
+        };
+        self.agenda.push((i, Pushdown::new()));
+    }
+
+    /// Appends the token `t` to the input.
+    /// No parses are found after a token that the automaton does not know was pushed.
+    pub fn push_token(&mut self, t: T) {
+        self.accepted.clear();
+        match self.automaton.terminal_to_int(&t) {
+            Some(t_int) if !self.unknown_token => self.word.push(t_int),
+            _ => {
+                self.unknown_token = true;
+                self.agenda.clear();
+                self.blocked.clear();
+                return;
+            },
+        }
+
+        let blocked = ::std::mem::replace(&mut self.blocked, Vec::new());
+        for (item, r) in blocked {
+            self.resume(item, r);
+        }
+    }
+
+    /// Checks whether the tokens that were pushed so far form a word that is accepted.
+    pub fn is_accepting(&mut self) -> bool {
+        !self.best_parses(1).is_empty()
+    }
+
+    /// Returns the (at most) `k` best parses of the tokens that were pushed so far.
+    /// Like `recognise`, this does not terminate if there are fewer than `k` parses but
+    /// infinitely many items.
+    pub fn best_parses(&mut self, k: usize)
+                       -> Vec<Item<<A::I as Instruction>::Storage, A::I, T, W>>
+    {
+        while self.accepted.len() < k {
+            match self.agenda.pop() {
+                Some(item) => {
+                    self.expand(&item);
+                    if item.0.node == self.word.len() && A::is_terminal(&item.0.configuration) {
+                        self.accepted.push(item);
+                    }
+                },
+                None => break,
+            }
+        }
+
+        self.accepted
+            .iter()
+            .take(k)
+            .map(|&(ref c, ref run)| self.automaton.item_map(&(c.configuration.clone(), run.clone())))
+            .collect()
+    }
+
+    /// Applies every transition to `item` that matches its storage.
+    fn expand(&mut self, item: &IncrementalItem<A, T, W>) {
+        let transition_map = self.transition_map.clone();
+        if let Some(rs) = transition_map.get(A::extract_key(&item.0.configuration)) {
+            for r in rs {
+                self.resume(item.clone(), r.clone());
+            }
+        }
+    }
+
+    /// Applies the transition `r` to `item` if the tokens it reads are available, and puts it
+    /// aside if they may still be pushed.
+    fn resume(&mut self,
+              item: IncrementalItem<A, T, W>,
+              r: Transition<A::IInt, A::TInt, W>)
+    {
+        let position = item.0.node;
+        if position + r.word.len() <= self.word.len() {
+            if self.word[position..].starts_with(&r.word) {
+                let (c, run) = item;
+                for storage in r.instruction.apply(c.configuration.storage.clone()) {
+                    let c1 = LatticeConfiguration {
+                        node: position + r.word.len(),
+                        configuration: Configuration {
+                            word: Vec::new(),
+                            storage,
+                            weight: c.configuration.weight * r.weight,
+                        },
+                    };
+                    self.agenda.push((c1, run.clone().push(r.clone())));
+                }
+            }
+        } else if r.word.starts_with(&self.word[position..]) {
+            self.blocked.push((item, r));
+        }
+    }
+}
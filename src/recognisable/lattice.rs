@@ -0,0 +1,598 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::ops::{Mul, MulAssign};
+use std::rc::Rc;
+use std::str::{FromStr, from_utf8};
+
+use nom::{IResult, is_space};
+use num_traits::One;
+
+use recognisable::{Configuration, GaveUp, Instruction, Recogniser, SearchOptions, Transition};
+use recognisable::automaton::{Automaton, BeamMode};
+use util::agenda::{Agenda, BoundedPriorityQueue, KeyedBeamAgenda, ThresholdAgenda, Weighted};
+use util::parsing::{parse_token, parse_vec};
+use util::push_down::Pushdown;
+
+/// A weighted word lattice, i.e. a directed acyclic graph whose nodes are natural numbers and
+/// whose edges are labelled with terminal symbols of type `T` and weights of type `W`.
+/// Each path from the `initial` node to one of the `finals` represents a word.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lattice<T, W> {
+    pub initial: usize,
+    pub finals: Vec<usize>,
+    edges: Vec<Vec<LatticeEdge<T, W>>>,
+}
+
+/// An edge of a `Lattice` that leads to the node `target` by reading `label`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatticeEdge<T, W> {
+    pub label: T,
+    pub weight: W,
+    pub target: usize,
+}
+
+impl<T, W> Lattice<T, W> {
+    /// Creates a `Lattice` without any edges.
+    pub fn new(initial: usize, finals: Vec<usize>) -> Self {
+        Lattice {
+            initial,
+            finals,
+            edges: Vec::new(),
+        }
+    }
+
+    /// Adds an edge from `source` to `target` that reads `label` with the given `weight`.
+    pub fn add_edge(&mut self, source: usize, label: T, weight: W, target: usize) {
+        while self.edges.len() <= source {
+            self.edges.push(Vec::new());
+        }
+        self.edges[source].push(LatticeEdge { label, weight, target });
+    }
+
+    /// Returns the outgoing edges of `node`.
+    pub fn edges(&self, node: usize) -> &[LatticeEdge<T, W>] {
+        self.edges.get(node).map(|es| es.as_slice()).unwrap_or(&[])
+    }
+
+    /// Checks whether the `Lattice` may stop in `node`.
+    pub fn is_final(&self, node: usize) -> bool {
+        self.finals.contains(&node)
+    }
+
+    /// Returns the nodes that can be reached from `node` by reading `word` together with the
+    /// weight of the respective path.
+    pub fn read(&self, node: usize, word: &[T]) -> Vec<(usize, W)>
+        where T: PartialEq,
+              W: Copy + Mul<Output=W> + One,
+    {
+        let mut frontier = vec![(node, W::one())];
+        for t in word {
+            let mut new_frontier = Vec::new();
+            for (n, weight) in frontier {
+                for edge in self.edges(n) {
+                    if edge.label == *t {
+                        new_frontier.push((edge.target, weight * edge.weight));
+                    }
+                }
+            }
+            frontier = new_frontier;
+        }
+        frontier
+    }
+
+    /// Applies `f` to every label of the `Lattice`.
+    pub fn map<F, U>(&self, f: &mut F) -> Lattice<U, W>
+        where F: FnMut(&T) -> U,
+              W: Clone,
+    {
+        let mut edges = Vec::new();
+        for es in &self.edges {
+            let mut new_es = Vec::new();
+            for e in es {
+                new_es.push(LatticeEdge {
+                    label: f(&e.label),
+                    weight: e.weight.clone(),
+                    target: e.target,
+                });
+            }
+            edges.push(new_es);
+        }
+
+        Lattice {
+            initial: self.initial,
+            finals: self.finals.clone(),
+            edges,
+        }
+    }
+
+    /// Applies `f` to every label of the `Lattice` and removes the edges for which `f` returns
+    /// `None`.
+    pub fn filter_map<F, U>(&self, f: &mut F) -> Lattice<U, W>
+        where F: FnMut(&T) -> Option<U>,
+              W: Clone,
+    {
+        let mut edges = Vec::new();
+        for es in &self.edges {
+            let mut new_es = Vec::new();
+            for e in es {
+                if let Some(label) = f(&e.label) {
+                    new_es.push(LatticeEdge {
+                        label,
+                        weight: e.weight.clone(),
+                        target: e.target,
+                    });
+                }
+            }
+            edges.push(new_es);
+        }
+
+        Lattice {
+            initial: self.initial,
+            finals: self.finals.clone(),
+            edges,
+        }
+    }
+}
+
+/// The linear `Lattice` that represents exactly the given word.
+impl<T, W: One> From<Vec<T>> for Lattice<T, W> {
+    fn from(word: Vec<T>) -> Self {
+        let mut lattice = Lattice::new(0, vec![word.len()]);
+        for (i, t) in word.into_iter().enumerate() {
+            lattice.add_edge(i, t, W::one(), i + 1);
+        }
+        lattice
+    }
+}
+
+impl<T, W> FromStr for Lattice<T, W>
+    where T: FromStr,
+          T::Err: Debug,
+          W: FromStr + One,
+          W::Err: Debug,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut initial = None;
+        let mut finals = None;
+        let mut edges = Vec::new();
+
+        for l in s.lines() {
+            let l = l.trim();
+            if l.is_empty() || l.starts_with("%") {
+                continue;
+            } else if l.starts_with("initial:") {
+                match l[8..].split('%').next().unwrap().trim().parse() {
+                    Ok(i) => initial = Some(i),
+                    Err(_) => return Err(format!("Malformed declaration of initial node: \'{}\'", l)),
+                }
+            } else if l.starts_with("final:") {
+                match parse_vec(l[6..].trim_left().as_bytes(), parse_token, "[", "]", ",") {
+                    IResult::Done(_, result) => finals = Some(result),
+                    _ => return Err(format!("Malformed declaration of final nodes: \'{}\'", l)),
+                }
+            } else {
+                match parse_lattice_edge(l.as_bytes()) {
+                    IResult::Done(_, edge) => edges.push(edge),
+                    _ => return Err(format!("Could not parse \'{}\'", l)),
+                }
+            }
+        }
+
+        match (initial, finals) {
+            (Some(initial), Some(finals)) => {
+                let mut lattice = Lattice::new(initial, finals);
+                for (source, target, label, weight) in edges {
+                    lattice.add_edge(source, label, weight, target);
+                }
+                Ok(lattice)
+            },
+            (None, _) => Err("No initial node declared".to_string()),
+            (_, None) => Err("No final nodes declared".to_string()),
+        }
+    }
+}
+
+/// Parses a line of the form `source target label # weight` where the weight is optional.
+fn parse_lattice_edge<T, W>(input: &[u8]) -> IResult<&[u8], (usize, usize, T, W)>
+    where T: FromStr,
+          T::Err: Debug,
+          W: FromStr + One,
+          W::Err: Debug,
+{
+    do_parse!(
+        input,
+        source: parse_token >>
+        take_while!(is_space) >>
+        target: parse_token >>
+        take_while!(is_space) >>
+        label: parse_token >>
+        take_while!(is_space) >>
+        weight_o: opt!(
+            complete!(
+                do_parse!(
+                    tag!("#") >>
+                    take_while!(is_space) >>
+                    weight_s: map_res!(is_not!(" "), from_utf8) >>
+                    weight: expr_res!(weight_s.parse()) >>
+                    (weight)
+                )
+            )
+        ) >>
+        take_while!(is_space) >>
+        alt!(
+            eof!() |
+            preceded!(tag!("%"), take_while!(|_| true))
+        ) >>
+        ((source, target, label, weight_o.unwrap_or(W::one())))
+    )
+}
+
+impl<T: Display, W: Display> Display for Lattice<T, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buffer = String::new();
+        let mut iter = self.finals.iter().peekable();
+        while let Some(node) = iter.next() {
+            buffer.push_str(&node.to_string());
+            if iter.peek().is_some() {
+                buffer.push_str(", ");
+            }
+        }
+        write!(f, "initial: {}\nfinal: [{}]\n", self.initial, buffer)?;
+
+        for (source, es) in self.edges.iter().enumerate() {
+            for e in es {
+                write!(f, "{} {} \"{}\" # {}\n", source, e.target, e.label, e.weight)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configuration of an automaton that reads a `Lattice`.
+/// Instead of the remaining word, it records the `node` of the lattice that was reached so far.
+/// The `word` of the wrapped `configuration` is always empty.
+#[derive(Clone, Debug)]
+pub struct LatticeConfiguration<S, T, W> {
+    pub node: usize,
+    pub configuration: Configuration<S, T, W>,
+}
+
+impl<S: Hash, T: Hash, W> Hash for LatticeConfiguration<S, T, W> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash(state);
+        self.configuration.hash(state);
+    }
+}
+
+/// `impl` of `PartialEq` that ignores the `weight` (to conform to the `impl` of `Hash`)
+impl<S: PartialEq, T: PartialEq, W> PartialEq for LatticeConfiguration<S, T, W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node && self.configuration == other.configuration
+    }
+}
+
+impl<S: Eq, T: Eq, W> Eq for LatticeConfiguration<S, T, W> {}
+
+impl<S, T, W> PartialOrd for LatticeConfiguration<S, T, W>
+    where S: PartialOrd + Eq,
+          T: PartialOrd + Eq,
+          W: PartialOrd + Eq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.configuration.weight.partial_cmp(&other.configuration.weight) {
+            None | Some(Ordering::Equal) =>
+                match self.node.partial_cmp(&other.node) {
+                    None | Some(Ordering::Equal) =>
+                        self.configuration.partial_cmp(&other.configuration),
+                    x => x,
+                },
+            x => x,
+        }
+    }
+}
+
+impl<S, T, W> Ord for LatticeConfiguration<S, T, W>
+    where S: Eq + Ord,
+          T: Eq + Ord,
+          W: Eq + Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.configuration.weight.cmp(&other.configuration.weight) {
+            Ordering::Equal =>
+                match self.node.cmp(&other.node) {
+                    Ordering::Equal => self.configuration.cmp(&other.configuration),
+                    x => x,
+                },
+            x => x,
+        }
+    }
+}
+
+impl<S, T, W> Display for LatticeConfiguration<S, T, W>
+    where S: Display,
+          T: Display,
+          W: Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "node: {}\n{}", self.node, self.configuration)
+    }
+}
+
+/// items of the transition system that reads a `Lattice`
+pub type LatticeItem<S, I, T, W> = (LatticeConfiguration<S, T, W>, Pushdown<Transition<I, T, W>>);
+
+impl<S, I: Instruction<Storage=S>, T, W: Clone> Weighted for LatticeItem<S, I, T, W> {
+    type Weight = W;
+
+    fn get_weight(&self) -> W {
+        self.0.configuration.weight.clone()
+    }
+}
+
+/// Something that recognises the words of a weighted `Lattice`.
+pub trait LatticeRecognisable<T, W> {
+    type Parse;
+
+    fn recognise_lattice<'a>(&'a self, lattice: Lattice<T, W>) -> Box<Iterator<Item=Self::Parse> + 'a>;
+}
+
+/// Applies the transition `r` to the configuration `c`, where `c` reads the given `lattice`.
+fn apply_on_lattice<I, T, W>(lattice: &Lattice<T, W>,
+                             c: &LatticeConfiguration<I::Storage, T, W>,
+                             r: &Transition<I, T, W>)
+                             -> Vec<LatticeConfiguration<I::Storage, T, W>>
+    where I: Instruction,
+          I::Storage: Clone,
+          T: PartialEq,
+          W: Copy + Mul<Output=W> + One,
+{
+    let targets = lattice.read(c.node, &r.word);
+    if targets.is_empty() {
+        return Vec::new();
+    }
+
+    let mut confs = Vec::new();
+    for s1 in r.instruction.apply(c.configuration.storage.clone()) {
+        for &(node, path_weight) in &targets {
+            confs.push(
+                LatticeConfiguration {
+                    node,
+                    configuration: Configuration {
+                        word: Vec::new(),
+                        storage: s1.clone(),
+                        weight: c.configuration.weight * r.weight * path_weight,
+                    },
+                }
+            );
+        }
+    }
+
+    confs
+}
+
+fn extract_lattice_key<A, T, W>(c: &LatticeConfiguration<<A::IInt as Instruction>::Storage, A::TInt, W>)
+                                 -> &A::Key
+    where A: Automaton<T, W>,
+          A::I: Clone + Instruction,
+          A::IInt: Clone + Eq + Instruction,
+          T: Clone,
+          W: Clone + MulAssign + One,
+{
+    A::extract_key(&c.configuration)
+}
+
+/// Recognises all words of the given `lattice` with the automaton `a`.
+/// The weight of each parse is the product of the weights of the applied transitions and of the
+/// weights of the lattice edges that were read.
+pub fn recognise_lattice<'a, A, T, W>(a: &'a A, lattice: Lattice<T, W>)
+                                      -> Box<Iterator<Item=LatticeItem<<A::I as Instruction>::Storage, A::I, T, W>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    Box::new(lattice_recogniser_with_agenda(a, BinaryHeap::new(), lattice, SearchOptions::default()))
+}
+
+
+/// Like `recognise_lattice`, but the search is configured by the given `SearchOptions`, and an
+/// `Err` is yielded if the search was aborted because one of the `SearchLimits` was exceeded.
+pub fn recognise_lattice_bounded<'a, A, T, W>(a: &'a A, lattice: Lattice<T, W>, options: SearchOptions)
+                                              -> Box<Iterator<Item=Result<LatticeItem<<A::I as Instruction>::Storage, A::I, T, W>, GaveUp>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    Box::new(lattice_recogniser_with_agenda(a, BinaryHeap::new(), lattice, options).bounded())
+}
+
+
+/// Like `recognise_lattice_bounded`, but the beam is pruned according to the given `BeamMode`.
+/// With `BeamMode::Position`, the items that have reached the same node of the `lattice` share
+/// a beam, and with `BeamMode::Threshold`, they are compared with each other.
+pub fn recognise_lattice_beam_mode_bounded<'a, A, T, W>(a: &'a A, mode: BeamMode<W>, lattice: Lattice<T, W>, options: SearchOptions)
+                                                        -> Box<Iterator<Item=Result<LatticeItem<<A::I as Instruction>::Storage, A::I, T, W>, GaveUp>> + 'a>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a + Clone,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+{
+    match mode {
+        BeamMode::Width(beam) =>
+            Box::new(lattice_recogniser_with_agenda(a, BoundedPriorityQueue::new(beam), lattice, options).bounded()),
+        BeamMode::Threshold(threshold) => {
+            let agenda = ThresholdAgenda::new(
+                threshold,
+                Box::new(lattice_node::<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>)
+            );
+            Box::new(lattice_recogniser_with_agenda(a, agenda, lattice, options).bounded())
+        },
+        BeamMode::Position(beam) => {
+            let agenda = KeyedBeamAgenda::new(
+                beam,
+                Box::new(lattice_node::<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>)
+            );
+            Box::new(lattice_recogniser_with_agenda(a, agenda, lattice, options).bounded())
+        },
+        BeamMode::StorageKey(beam) => {
+            let agenda = KeyedBeamAgenda::new(beam, Box::new(lattice_storage_key::<A, T, W>));
+            Box::new(lattice_recogniser_with_agenda(a, agenda, lattice, options).bounded())
+        },
+    }
+}
+
+
+fn lattice_node<S, I, T, W>(item: &LatticeItem<S, I, T, W>) -> usize {
+    item.0.node
+}
+
+
+fn lattice_storage_key<A, T, W>(item: &LatticeItem<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>) -> A::Key
+    where A: Automaton<T, W>,
+          A::I: Clone + Instruction,
+          A::IInt: Clone + Eq + Instruction,
+          A::Key: Clone,
+          T: Clone,
+          W: Clone + MulAssign + One,
+{
+    A::extract_key(&item.0.configuration).clone()
+}
+
+
+/// Creates a `Recogniser` for the automaton `a` and the `lattice` that uses the given `agenda`.
+pub fn lattice_recogniser_with_agenda<'a, A, T, W, Ag>(a: &'a A, agenda: Ag, lattice: Lattice<T, W>, options: SearchOptions)
+                                                       -> Recogniser<'a,
+                                                                     Ag,
+                                                                     LatticeConfiguration<<A::IInt as Instruction>::Storage, A::TInt, W>,
+                                                                     Transition<A::IInt, A::TInt, W>,
+                                                                     A::Key,
+                                                                     LatticeItem<<A::I as Instruction>::Storage, A::I, T, W>>
+    where A: Automaton<T, W>,
+          A::I: Clone + Eq + Instruction,
+          <A::I as Instruction>::Storage: Clone + Eq,
+          A::IInt: 'a + Ord,
+          A::Key: 'a,
+          <A::IInt as Instruction>::Storage: Clone + Eq + Ord + 'a,
+          T: Clone + Eq + Ord + 'a,
+          A::TInt: Clone + Eq + Ord + 'a,
+          W: Copy + Mul<Output=W> + MulAssign + One + Ord + 'a,
+          Ag: Agenda<Item=LatticeItem<<A::IInt as Instruction>::Storage, A::IInt, A::TInt, W>>,
+{
+    let lattice_int = Rc::new(lattice.filter_map(&mut |t| a.terminal_to_int(t)));
+    let lattice_apply = lattice_int.clone();
+
+    let i = LatticeConfiguration {
+        node: lattice_int.initial,
+        configuration: Configuration {
+            word: Vec::new(),
+            storage: a.initial_int(),
+            weight: W::one(),
+        },
+    };
+
+    let mut agenda = agenda;
+    agenda.enqueue((i, Pushdown::new()));
+
+    let closed_set = options.closed_set;
+    let mut recogniser = Recogniser::new(
+        agenda,
+        Box::new(extract_lattice_key::<A, T, W>),
+        a.transition_map(),
+        Box::new(move |c, r| apply_on_lattice(&lattice_apply, c, r)),
+        Box::new(move |c| lattice_int.is_final(c.node) && A::is_terminal(&c.configuration)),
+        Box::new(move |&(ref c, ref run)| {
+            let (configuration, run) = a.item_map(&(c.configuration.clone(), run.clone()));
+            (LatticeConfiguration { node: c.node, configuration }, run)
+        }),
+        options,
+    );
+    recogniser.closed_set = closed_set.map(lattice_closed_set_filter);
+    recogniser
+}
+
+
+/// Returns a predicate that holds for the first `k` `LatticeConfiguration`s with the same
+/// storage and node it is called with.
+fn lattice_closed_set_filter<'a, S, T, W>(k: usize) -> Box<FnMut(&LatticeConfiguration<S, T, W>) -> bool + 'a>
+    where S: Clone + Ord + 'a,
+          T: 'a,
+          W: 'a,
+{
+    let mut expanded: BTreeMap<(usize, S), usize> = BTreeMap::new();
+    Box::new(move |c| {
+        let count = expanded.entry((c.node, c.configuration.storage.clone())).or_insert(0);
+        *count += 1;
+        *count <= k
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lattice_from_str() {
+        let mut control_lattice = Lattice::new(0, vec![2, 3]);
+        control_lattice.add_edge(0, String::from("a"), 0.5, 1);
+        control_lattice.add_edge(0, String::from("b"), 0.5, 1);
+        control_lattice.add_edge(1, String::from("c d"), 1.0, 2);
+        control_lattice.add_edge(1, String::from("c"), 0.25, 3);
+
+        let lattice_string = "% a small lattice\n\
+                              initial: 0\n\
+                              final: [2, 3]\n\
+                              0 1 a # 0.5\n\
+                              0 1 b # 0.5\n\
+                              1 2 \"c d\"\n\
+                              1 3 c # 0.25  % comment\n";
+
+        assert_eq!(Ok(control_lattice.clone()), lattice_string.parse());
+        assert_eq!(Ok(control_lattice.clone()), control_lattice.to_string().parse());
+
+        assert!("final: [1]\n0 1 a".parse::<Lattice<String, f64>>().is_err());
+        assert!("initial: 0\nfinal: [1]\n0 a".parse::<Lattice<String, f64>>().is_err());
+    }
+
+    #[test]
+    fn test_lattice_read() {
+        let mut lattice = Lattice::new(0, vec![3]);
+        lattice.add_edge(0, 'a', 0.5, 1);
+        lattice.add_edge(0, 'a', 0.25, 2);
+        lattice.add_edge(1, 'b', 1.0, 3);
+        lattice.add_edge(2, 'b', 1.0, 3);
+        lattice.add_edge(2, 'c', 1.0, 3);
+
+        assert_eq!(vec![(3, 0.5), (3, 0.25)], lattice.read(0, &['a', 'b']));
+        assert_eq!(vec![(3, 0.25)], lattice.read(0, &['a', 'c']));
+        assert_eq!(vec![(2, 1.0)], lattice.read(2, &[]));
+        assert!(lattice.read(1, &['c']).is_empty());
+    }
+
+    #[test]
+    fn test_linear_lattice() {
+        let lattice: Lattice<char, f64> = Lattice::from(vec!['a', 'b']);
+        assert_eq!(vec![(2, 1.0)], lattice.read(0, &['a', 'b']));
+        assert!(lattice.is_final(2));
+        assert!(!lattice.is_final(1));
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+use std::str::{FromStr, from_utf8};
+
+use nom::{IResult, is_space};
+use num_traits::One;
+
+use recognisable::lattice::Lattice;
+use util::parsing::parse_token;
+use util::unknown_words::{UNKNOWN_WORD, signature};
+
+/// A weighted lexicon that maps words to preterminals of type `N`.
+/// It is used to parse sentences of words with a grammar whose terminal symbols are preterminals
+/// (e.g. part-of-speech tags): each sentence is turned into a `Lattice` over preterminals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lexicon<N, W> {
+    entries: BTreeMap<String, Vec<(N, W)>>,
+}
+
+impl<N, W> Lexicon<N, W> {
+    pub fn new() -> Self {
+        Lexicon { entries: BTreeMap::new() }
+    }
+
+    /// Adds the entry that `word` is read as `preterminal` with the given `weight`.
+    pub fn insert(&mut self, word: String, preterminal: N, weight: W) {
+        self.entries.entry(word).or_insert_with(Vec::new).push((preterminal, weight));
+    }
+
+    /// Adds all entries of `other`.
+    pub fn append(&mut self, other: Lexicon<N, W>) {
+        for (word, mut entries) in other.entries {
+            self.entries.entry(word).or_insert_with(Vec::new).append(&mut entries);
+        }
+    }
+
+    /// Returns the preterminals of `word` with their weights.
+    pub fn get(&self, word: &str) -> &[(N, W)] {
+        self.entries.get(word).map(|es| es.as_slice()).unwrap_or(&[])
+    }
+
+    /// Returns the preterminals of `word`, of its signature if it has none, or of `UNK` if the
+    /// signature has none either, cf. `util::unknown_words`.
+    pub fn lookup(&self, word: &str) -> &[(N, W)] {
+        if self.entries.contains_key(word) {
+            self.get(word)
+        } else if self.entries.contains_key(&signature(word)) {
+            self.get(&signature(word))
+        } else {
+            self.get(UNKNOWN_WORD)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the `Lattice` whose edges from node `i` to node `i + 1` read the preterminals of
+    /// the `i`th word of the `sentence` (as returned by `lookup`).
+    pub fn to_lattice(&self, sentence: &[String]) -> Lattice<N, W>
+        where N: Clone,
+              W: Clone,
+    {
+        let mut lattice = Lattice::new(0, vec![sentence.len()]);
+        for (i, word) in sentence.iter().enumerate() {
+            for &(ref preterminal, ref weight) in self.lookup(word) {
+                lattice.add_edge(i, preterminal.clone(), weight.clone(), i + 1);
+            }
+        }
+        lattice
+    }
+}
+
+impl<N, W> Default for Lexicon<N, W> {
+    fn default() -> Self {
+        Lexicon::new()
+    }
+}
+
+/// Returns the linear `Lattice` over the tags of a sentence of tokens of the form `word/TAG`.
+///
+/// ```
+/// use rustomata::recognisable::lattice::Lattice;
+/// use rustomata::recognisable::lexicon::tagged_to_lattice;
+///
+/// let sentence = vec!["the/DT".to_string(), "dog/NN".to_string()];
+/// let lattice: Lattice<String, f64> = tagged_to_lattice(&sentence).unwrap();
+///
+/// assert_eq!(Lattice::from(vec!["DT".to_string(), "NN".to_string()]), lattice);
+/// ```
+pub fn tagged_to_lattice<W: One>(sentence: &[String]) -> Result<Lattice<String, W>, String> {
+    let mut tags = Vec::new();
+    for token in sentence {
+        match token.rfind('/') {
+            Some(i) if i > 0 && i + 1 < token.len() => tags.push(token[i + 1..].to_string()),
+            _ => return Err(format!("Token \'{}\' is not of the form \'word/TAG\'", token)),
+        }
+    }
+    Ok(Lattice::from(tags))
+}
+
+/// Returns the words of a sentence of tokens of the form `word/TAG`, cf. `tagged_to_lattice`.
+/// Tokens that are not of this form are kept.
+///
+/// ```
+/// use rustomata::recognisable::lexicon::tagged_words;
+///
+/// let sentence = vec!["the/DT".to_string(), "1/2/CD".to_string()];
+///
+/// assert_eq!(vec!["the".to_string(), "1/2".to_string()], tagged_words(&sentence));
+/// ```
+pub fn tagged_words(sentence: &[String]) -> Vec<String> {
+    sentence.iter().map(|token| match token.rfind('/') {
+        Some(i) if i > 0 && i + 1 < token.len() => token[..i].to_string(),
+        _ => token.clone(),
+    }).collect()
+}
+
+/// Reads one entry `word preterminal # weight` per line, where the weight is optional.
+/// Empty lines and lines starting with `%` are ignored.
+impl<N, W> FromStr for Lexicon<N, W>
+    where N: FromStr,
+          N::Err: Debug,
+          W: FromStr + One,
+          W::Err: Debug,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lexicon = Lexicon::new();
+
+        for l in s.lines() {
+            let l = l.trim();
+            if l.is_empty() || l.starts_with('%') {
+                continue;
+            }
+            match parse_lexicon_entry(l.as_bytes()) {
+                IResult::Done(_, (word, preterminal, weight)) => lexicon.insert(word, preterminal, weight),
+                _ => return Err(format!("Could not parse \'{}\'", l)),
+            }
+        }
+
+        Ok(lexicon)
+    }
+}
+
+fn parse_lexicon_entry<N, W>(input: &[u8]) -> IResult<&[u8], (String, N, W)>
+    where N: FromStr,
+          N::Err: Debug,
+          W: FromStr + One,
+          W::Err: Debug,
+{
+    do_parse!(
+        input,
+        word: parse_token >>
+        take_while!(is_space) >>
+        preterminal: parse_token >>
+        take_while!(is_space) >>
+        weight_o: opt!(
+            complete!(
+                do_parse!(
+                    tag!("#") >>
+                    take_while!(is_space) >>
+                    weight_s: map_res!(is_not!(" "), from_utf8) >>
+                    weight: expr_res!(weight_s.parse()) >>
+                    (weight)
+                )
+            )
+        ) >>
+        take_while!(is_space) >>
+        alt!(
+            eof!() |
+            preceded!(tag!("%"), take_while!(|_| true))
+        ) >>
+        ((word, preterminal, weight_o.unwrap_or(W::one())))
+    )
+}
+
+impl<N: Display, W: Display> Display for Lexicon<N, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (word, entries) in &self.entries {
+            for &(ref preterminal, ref weight) in entries {
+                writeln!(f, "\"{}\" \"{}\" # {}", word, preterminal, weight)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lexicon() {
+        let lexicon: Lexicon<String, f64> = "% a small lexicon\n\
+                                             the DT\n\
+                                             dog NN # 0.75\n\
+                                             dog VB # 0.25\n\
+                                             UNK NN # 0.5".parse().unwrap();
+
+        assert_eq!(&[("NN".to_string(), 0.75), ("VB".to_string(), 0.25)], lexicon.get("dog"));
+        assert_eq!(&[("NN".to_string(), 0.5)], lexicon.lookup("cat"));
+        assert_eq!(lexicon, lexicon.to_string().parse().unwrap());
+
+        let lattice = lexicon.to_lattice(&["the".to_string(), "dog".to_string()]);
+        assert_eq!(vec![2], lattice.finals);
+        assert_eq!(1, lattice.edges(0).len());
+        assert_eq!(2, lattice.edges(1).len());
+        assert!(lattice.edges(2).is_empty());
+    }
+}
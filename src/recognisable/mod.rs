@@ -1,9 +1,15 @@
 mod configuration;
 mod recogniser;
+mod search;
 mod transition;
 
 pub mod automaton;
+pub mod batch;
+pub mod chart_pruning;
 pub mod from_str;
+pub mod incremental;
+pub mod lattice;
+pub mod lexicon;
 #[macro_use]
 pub mod coarse_to_fine;
 
@@ -12,7 +18,8 @@ use util::agenda::Weighted;
 use util::push_down::Pushdown;
 
 pub use self::configuration::Configuration;
-pub use self::recogniser::Recogniser;
+pub use self::recogniser::{BoundedRecogniser, Recogniser};
+pub use self::search::{GaveUp, SearchLimits, SearchOptions, SearchStatistics};
 pub use self::transition::Transition;
 
 
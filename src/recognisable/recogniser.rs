@@ -1,41 +1,173 @@
+use std::cell::RefCell;
 use std::collections::{BinaryHeap, HashMap};
 use std::hash::Hash;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use recognisable::{GaveUp, SearchLimits, SearchOptions, SearchStatistics};
 use util::agenda::Agenda;
 use util::push_down::Pushdown;
 
 /// Iterator for `recognise` that creates new solutions with every step
 pub struct Recogniser<'a, A, C, R: Ord, K: Hash, O> {  // TODO rename to ParseForest
     pub agenda: A,
-    pub configuration_characteristic: Box<Fn(&C) -> &K>,
-    pub filtered_rules: Rc<HashMap<K, BinaryHeap<R>>>,
-    pub apply: Box<Fn(&C, &R) -> Vec<C>>,
-    pub accepting: Box<Fn(&C) -> bool>,
+    pub configuration_characteristic: Box<Fn(&C) -> &K + 'a>,
+    pub filtered_rules: Arc<HashMap<K, BinaryHeap<R>>>,
+    pub apply: Box<Fn(&C, &R) -> Vec<C> + 'a>,
+    pub accepting: Box<Fn(&C) -> bool + 'a>,
     pub item_map: Box<Fn(&(C, Pushdown<R>)) -> O + 'a>,
+    pub statistics: Option<Rc<RefCell<SearchStatistics>>>,
+    pub limits: SearchLimits,
+    /// returns `false` for configurations that are dominated by already expanded ones
+    pub closed_set: Option<Box<FnMut(&C) -> bool + 'a>>,
+    dequeued: usize,
+    elapsed: Duration,
+    gave_up: Option<GaveUp>,
 }
 
-impl<'a, A, C, R, K, O> Iterator for Recogniser<'a, A, C, R, K, O>
+impl<'a, A, C, R, K, O> Recogniser<'a, A, C, R, K, O>
     where A: Agenda<Item=(C, Pushdown<R>)>,
           C: Clone + Ord,
           R: Clone + Ord,
           K: Eq + Hash,
 {
-    type Item = O;
-    fn next(&mut self) -> Option<Self::Item> {
-        while let Some((c, run)) = self.agenda.dequeue() {
+    pub fn new(agenda: A,
+               configuration_characteristic: Box<Fn(&C) -> &K + 'a>,
+               filtered_rules: Arc<HashMap<K, BinaryHeap<R>>>,
+               apply: Box<Fn(&C, &R) -> Vec<C> + 'a>,
+               accepting: Box<Fn(&C) -> bool + 'a>,
+               item_map: Box<Fn(&(C, Pushdown<R>)) -> O + 'a>,
+               options: SearchOptions)
+               -> Self
+    {
+        Recogniser {
+            agenda,
+            configuration_characteristic,
+            filtered_rules,
+            apply,
+            accepting,
+            item_map,
+            statistics: options.statistics,
+            limits: options.limits,
+            closed_set: None,
+            dequeued: 0,
+            elapsed: Duration::new(0, 0),
+            gave_up: None,
+        }
+    }
+
+    /// Returns the reason why the search was aborted, if it was.
+    pub fn gave_up(&self) -> Option<GaveUp> {
+        self.gave_up
+    }
+
+    /// Searches for the next solution.
+    /// If one of the `limits` is exceeded, the search is aborted and the reason is returned
+    /// (once) as an `Err`.
+    pub fn next_parse(&mut self) -> Result<Option<O>, GaveUp> {
+        if self.gave_up.is_some() {
+            return Ok(None);
+        }
+
+        let start = Instant::now();
+        let result = self.search(&start);
+        let elapsed = start.elapsed();
+        self.elapsed += elapsed;
+        SearchStatistics::record(&self.statistics, |s| s.duration += elapsed);
+
+        if let Err(reason) = result {
+            self.gave_up = Some(reason);
+        }
+        result
+    }
+
+    /// Turns the `Recogniser` into an `Iterator` that also reports if the search was aborted.
+    pub fn bounded(self) -> BoundedRecogniser<'a, A, C, R, K, O> {
+        BoundedRecogniser(self)
+    }
+
+    fn exceeded_limit(&self, start: &Instant) -> Option<GaveUp> {
+        if let Some(max_items) = self.limits.max_items {
+            if self.dequeued >= max_items {
+                return Some(GaveUp::ItemLimit);
+            }
+        }
+        if let Some(max_agenda_size) = self.limits.max_agenda_size {
+            if self.agenda.size() > max_agenda_size {
+                return Some(GaveUp::AgendaLimit);
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.elapsed + start.elapsed() >= timeout {
+                return Some(GaveUp::Timeout);
+            }
+        }
+        None
+    }
+
+    fn search(&mut self, start: &Instant) -> Result<Option<O>, GaveUp> {
+        while !self.agenda.is_empty() {
+            if let Some(reason) = self.exceeded_limit(start) {
+                return Err(reason);
+            }
+
+            let (c, run) = self.agenda.dequeue().unwrap();
+            self.dequeued += 1;
+            SearchStatistics::record(&self.statistics, |s| s.dequeued += 1);
+            if let Some(ref mut closed_set) = self.closed_set {
+                if !closed_set(&c) {
+                    SearchStatistics::record(&self.statistics, |s| s.duplicates += 1);
+                    continue;
+                }
+            }
             if let Some(rs) = self.filtered_rules.get((self.configuration_characteristic)(&c)) {
                 for r in rs {
+                    SearchStatistics::record(&self.statistics, |s| s.transitions_applied += 1);
                     for c1 in (self.apply)(&c, r) {
                         let run1 = run.clone().push(r.clone());
-                        self.agenda.enqueue((c1, run1));
+                        let dropped = self.agenda.enqueue((c1, run1)).is_some();
+                        let size = self.agenda.size();
+                        SearchStatistics::record(&self.statistics, |s| s.record_enqueue(size, dropped));
                     }
                 }
             }
             if (self.accepting)(&c) {
-                return Some((self.item_map)(&(c, run)));
+                return Ok(Some((self.item_map)(&(c, run))));
             }
         }
 
-        None
+        Ok(None)
+    }
+}
+
+impl<'a, A, C, R, K, O> Iterator for Recogniser<'a, A, C, R, K, O>
+    where A: Agenda<Item=(C, Pushdown<R>)>,
+          C: Clone + Ord,
+          R: Clone + Ord,
+          K: Eq + Hash,
+{
+    type Item = O;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_parse().unwrap_or(None)
+    }
+}
+
+/// Iterator for a `Recogniser` that yields an `Err` if the search was aborted because one of
+/// its `SearchLimits` was exceeded.
+pub struct BoundedRecogniser<'a, A, C, R: Ord, K: Hash, O>(Recogniser<'a, A, C, R, K, O>);
+
+impl<'a, A, C, R, K, O> Iterator for BoundedRecogniser<'a, A, C, R, K, O>
+    where A: Agenda<Item=(C, Pushdown<R>)>,
+          C: Clone + Ord,
+          R: Clone + Ord,
+          K: Eq + Hash,
+{
+    type Item = Result<O, GaveUp>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0.next_parse() {
+            Ok(Some(parse)) => Some(Ok(parse)),
+            Ok(None) => None,
+            Err(reason) => Some(Err(reason)),
+        }
     }
 }
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Counters that are collected during the agenda-driven search of a recogniser.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStatistics {
+    /// number of items that were taken from the agenda
+    pub dequeued: usize,
+    /// number of pairs of an item and a matching transition that were tried
+    pub transitions_applied: usize,
+    /// number of items that were offered to the agenda
+    pub enqueued: usize,
+    /// number of items that were dropped by the agenda (e.g. by a `BoundedPriorityQueue`)
+    pub pruned: usize,
+    /// maximum number of items that were in the agenda at the same time
+    pub agenda_peak: usize,
+    /// number of items that were skipped because of the closed set
+    pub duplicates: usize,
+    /// time that was spent searching
+    pub duration: Duration,
+}
+
+impl SearchStatistics {
+    /// Applies `f` to the `statistics` of a search, if they are collected.
+    pub fn record<F>(statistics: &Option<Rc<RefCell<SearchStatistics>>>, f: F)
+        where F: FnOnce(&mut SearchStatistics)
+    {
+        if let Some(ref statistics) = *statistics {
+            f(&mut statistics.borrow_mut());
+        }
+    }
+
+    /// Records that an item was offered to an agenda that now contains `size` items.
+    pub fn record_enqueue(&mut self, size: usize, dropped: bool) {
+        self.enqueued += 1;
+        if dropped {
+            self.pruned += 1;
+        }
+        if size > self.agenda_peak {
+            self.agenda_peak = size;
+        }
+    }
+}
+
+impl fmt::Display for SearchStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dequeued: {}, transitions applied: {}, enqueued: {}, pruned: {}, agenda peak: {}, duplicates: {}, time: {}.{:03}s",
+            self.dequeued,
+            self.transitions_applied,
+            self.enqueued,
+            self.pruned,
+            self.agenda_peak,
+            self.duplicates,
+            self.duration.as_secs(),
+            self.duration.subsec_nanos() / 1_000_000
+        )
+    }
+}
+
+/// Bounds on the resources that a recogniser may use; `None` means unbounded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    /// maximum number of items that are taken from the agenda
+    pub max_items: Option<usize>,
+    /// maximum number of items that may be in the agenda at the same time
+    pub max_agenda_size: Option<usize>,
+    /// maximum time that is spent searching
+    pub timeout: Option<Duration>,
+}
+
+/// Reason why a recogniser aborted its search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GaveUp {
+    ItemLimit,
+    AgendaLimit,
+    Timeout,
+}
+
+impl fmt::Display for GaveUp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GaveUp::ItemLimit => write!(f, "item limit reached"),
+            GaveUp::AgendaLimit => write!(f, "agenda size limit reached"),
+            GaveUp::Timeout => write!(f, "timeout reached"),
+        }
+    }
+}
+
+/// Options for the agenda-driven search of a recogniser.
+#[derive(Clone, Default)]
+pub struct SearchOptions {
+    /// collects `SearchStatistics` if present
+    pub statistics: Option<Rc<RefCell<SearchStatistics>>>,
+    /// aborts the search when one of them is exceeded
+    pub limits: SearchLimits,
+    /// if present, at most this many configurations with the same storage and remaining word
+    /// are expanded; since the search is best-first, the skipped ones are dominated
+    pub closed_set: Option<usize>,
+}
@@ -251,6 +251,40 @@ pub fn to_abstract_syntax_tree<A>((tree_map, _): (GornTree<PosState<A>>, Vec<usi
     abstract_syntax_tree
 }
 
+/// Returns the derivation of a `PMCFG` that corresponds to an accepting run of the
+/// `TreeStackAutomaton` constructed from it, given the storage of the final configuration of the
+/// run.
+///
+/// ```
+/// extern crate log_domain;
+/// extern crate rustomata;
+///
+/// use log_domain::LogDomain;
+/// use rustomata::pmcfg::PMCFG;
+/// use rustomata::recognisable::Recognisable;
+/// use rustomata::tree_stack_automaton::{TreeStackAutomaton, to_derivation};
+///
+/// # fn main() {
+/// let grammar: PMCFG<String, String, LogDomain<f64>> = "initial: [S]\n\
+///                                                       S → [[T a, Var 0 0]] (A)\n\
+///                                                       A → [[T b]] ()".parse().unwrap();
+/// let automaton = TreeStackAutomaton::from(grammar);
+/// let parse = automaton.recognise(vec!["a".to_string(), "b".to_string()]).next().unwrap();
+///
+/// let derivation = to_derivation(&parse.0.storage);
+/// assert_eq!("S", derivation.get(&vec![]).unwrap().head);
+/// assert_eq!("A", derivation.get(&vec![0]).unwrap().head);
+/// # }
+/// ```
+pub fn to_derivation<N, T, W>(storage: &TreeStack<PosState<PMCFGRule<N, T, W>>>)
+                              -> GornTree<PMCFGRule<N, T, W>>
+    where N: Clone,
+          T: Clone,
+          W: Clone,
+{
+    to_abstract_syntax_tree(storage.to_tree())
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
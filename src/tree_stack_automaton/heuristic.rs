@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::ops::Mul;
+
+use num_traits::{One, Zero};
+
+use recognisable::{Configuration, Transition};
+use util::agenda::Heuristic;
+use util::push_down::Pushdown;
+
+/// `Heuristic` for (integerised) automata that estimates the weight of a `Configuration` by the
+/// best weights with which the symbols of its remaining word can be read.
+/// The storage is ignored, hence the heuristic can be used with any storage type, e.g. for
+/// `TreeStackAutomaton`s.
+///
+/// The estimate is admissible if no weight is greater than `W::one()` (e.g. for probabilities).
+/// For automata that are constructed from a `PMCFG`, the weights of the rules are collected by
+/// transitions that read no symbol, so the estimate only rules out configurations whose remaining
+/// word can not be read at all.
+#[derive(Debug, Clone)]
+pub struct WordHeuristic<W> {
+    reading: HashMap<usize, W>,
+}
+
+impl<W> WordHeuristic<W>
+    where W: Copy + One + Ord + Zero,
+{
+    /// Takes for each symbol the best weight of a transition that reads exactly this symbol.
+    /// Symbols that are read together with other symbols are estimated by `W::one()`.
+    pub fn new<'a, I, It>(transitions: It) -> Self
+        where It: IntoIterator<Item=&'a Transition<I, usize, W>>,
+              I: 'a,
+              W: 'a,
+    {
+        let mut reading = HashMap::new();
+        let mut unbounded = Vec::new();
+        for t in transitions {
+            if t.word.len() == 1 {
+                let best = reading.entry(t.word[0]).or_insert(t.weight);
+                if t.weight > *best {
+                    *best = t.weight;
+                }
+            } else {
+                unbounded.extend(t.word.iter().cloned());
+            }
+        }
+
+        for a in unbounded {
+            reading.insert(a, W::one());
+        }
+
+        WordHeuristic { reading }
+    }
+}
+
+impl<W> WordHeuristic<W>
+    where W: Copy + Mul<Output=W> + One + Zero,
+{
+    /// Returns the estimate for the given remaining word.
+    pub fn estimate_word(&self, word: &[usize]) -> W {
+        word.iter().fold(
+            W::one(),
+            |acc, a| acc * self.reading.get(a).cloned().unwrap_or_else(W::zero)
+        )
+    }
+}
+
+impl<S, W, R> Heuristic<(Configuration<S, usize, W>, Pushdown<R>)> for WordHeuristic<W>
+    where W: Copy + Mul<Output=W> + One + Zero,
+{
+    type Weight = W;
+
+    fn estimate(&self, item: &(Configuration<S, usize, W>, Pushdown<R>)) -> W {
+        self.estimate_word(&item.0.word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_heuristic() {
+        let transitions = vec![
+            Transition { word: vec![0], weight: 2, instruction: () },
+            Transition { word: vec![0], weight: 3, instruction: () },
+            Transition { word: vec![1, 2], weight: 5, instruction: () },
+            Transition { word: vec![2], weight: 7, instruction: () },
+        ];
+        let heuristic = WordHeuristic::new(&transitions);
+
+        assert_eq!(9, heuristic.estimate_word(&[0, 0]));
+        assert_eq!(3, heuristic.estimate_word(&[0, 1, 2]));
+        assert_eq!(0, heuristic.estimate_word(&[0, 3]));
+        assert_eq!(1, heuristic.estimate_word(&[]));
+    }
+}
@@ -5,23 +5,26 @@ use std::convert::From;
 use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
 use std::ops::{Mul, MulAssign};
-use std::rc::Rc;
+use std::sync::Arc;
 use std::vec::Vec;
 
-use num_traits::One;
+use num_traits::{One, Zero};
 
 use integeriser::{HashIntegeriser, Integeriser};
-use recognisable::{Configuration, Item, Recognisable, Transition};
-use recognisable::automaton::{Automaton, recognise, recognise_beam};
+use recognisable::{Configuration, Item, Recognisable, SearchOptions, Transition};
+use recognisable::automaton::{Automaton, recognise, recognise_astar, recognise_beam};
+use recognisable::lattice::{Lattice, LatticeItem, LatticeRecognisable, recognise_lattice};
 use util::integerisable::{Integerisable1, Integerisable2};
 use util::push_down::Pushdown;
 
 mod from_pmcfg;
 mod from_str;
+mod heuristic;
 mod tree_stack;
 mod tree_stack_instruction;
 
 pub use self::from_pmcfg::*;
+pub use self::heuristic::*;
 pub use self::tree_stack::*;
 pub use self::tree_stack_instruction::*;
 
@@ -37,7 +40,7 @@ pub struct TreeStackAutomaton<A, T, W>
 {
     a_integeriser: HashIntegeriser<A>,
     t_integeriser: HashIntegeriser<T>,
-    transitions: Rc<TransitionMap<usize, usize, W>>,
+    transitions: Arc<TransitionMap<usize, usize, W>>,
     initial: TreeStack<usize>,
 }
 
@@ -75,7 +78,7 @@ impl<A, T, W> TreeStackAutomaton<A, T, W>
         TreeStackAutomaton {
             a_integeriser: a_inter,
             t_integeriser: t_inter,
-            transitions: Rc::new(transition_map),
+            transitions: Arc::new(transition_map),
             initial: init,
         }
     }
@@ -131,6 +134,39 @@ impl<A, T, W> Recognisable<T, W> for TreeStackAutomaton<A, T, W>
 }
 
 
+impl<A, T, W> TreeStackAutomaton<A, T, W>
+    where A: Ord + PartialEq + Clone + Hash,
+          T: Clone + Eq + Hash + Ord,
+          W: One + Mul<Output=W> + MulAssign + Clone + Copy + Eq + Ord + Zero
+{
+    /// Returns the `WordHeuristic` for the transitions of the automaton.
+    pub fn word_heuristic(&self) -> WordHeuristic<W> {
+        WordHeuristic::new(self.transitions.values().flat_map(|h| h.iter()))
+    }
+
+    /// Like `recognise`, but the search is an A* search guided by the `WordHeuristic` of the
+    /// automaton.
+    pub fn recognise_astar<'a>(&'a self, word: Vec<T>, options: SearchOptions)
+                               -> Box<Iterator<Item=Item<TreeStack<A>, TreeStackInstruction<A>, T, W>> + 'a>
+    {
+        recognise_astar(self, self.word_heuristic(), word, options)
+    }
+}
+
+
+impl<A, T, W> LatticeRecognisable<T, W> for TreeStackAutomaton<A, T, W>
+    where A: Ord + PartialEq + Clone + Hash,
+          T: Clone + Eq + Hash + Ord,
+          W: One + Mul<Output=W> + MulAssign + Clone + Copy + Eq + Ord
+{
+    type Parse = LatticeItem<TreeStack<A>, TreeStackInstruction<A>, T, W>;
+
+    fn recognise_lattice<'a>(&'a self, lattice: Lattice<T, W>) -> Box<Iterator<Item=Self::Parse> + 'a> {
+        recognise_lattice(self, lattice)
+    }
+}
+
+
 impl<A, T, W> Automaton<T, W> for TreeStackAutomaton<A, T, W>
     where A: Clone + Eq + Hash + Ord,
           T: Clone + Eq + Hash + Ord,
@@ -182,8 +218,32 @@ impl<A, T, W> Automaton<T, W> for TreeStackAutomaton<A, T, W>
         }
     }
 
-    fn terminal_to_int(&self, t: &T) -> usize {
-        self.t_integeriser.find_key(t).unwrap()
+    fn terminal_to_int(&self, t: &T) -> Option<usize> {
+        self.t_integeriser.find_key(t)
+    }
+
+    fn int_to_terminal(&self, t: &usize) -> T {
+        self.t_integeriser.find_value(*t).unwrap().clone()
+    }
+
+    fn transition_to_int(&self, t: &Transition<TreeStackInstruction<A>, T, W>)
+                         -> Option<Transition<TreeStackInstruction<usize>, usize, W>>
+    {
+        let word = t.word.iter().map(|t| self.t_integeriser.find_key(t)).collect::<Option<Vec<_>>>()?;
+        let mut known = true;
+        let instruction = t.instruction.map(|a| match self.a_integeriser.find_key(a) {
+            Some(i) => i,
+            None => {
+                known = false;
+                0
+            },
+        });
+
+        if known {
+            Some(Transition { word, weight: t.weight, instruction })
+        } else {
+            None
+        }
     }
 
     fn extract_key(c: &Configuration<TreeStack<usize>, usize, W>) -> &usize {
@@ -196,7 +256,7 @@ impl<A, T, W> Automaton<T, W> for TreeStackAutomaton<A, T, W>
         c.word.is_empty() && c.storage.is_at_bottom()
     }
 
-    fn transition_map(&self) -> Rc<TransitionMap<usize, usize, W>> {
+    fn transition_map(&self) -> Arc<TransitionMap<usize, usize, W>> {
         self.transitions.clone()
     }
 
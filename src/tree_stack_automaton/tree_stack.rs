@@ -1,6 +1,6 @@
 use std::cmp::Ordering;
 use std::fmt;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::hash::Hash;
 
 use integeriser::{HashIntegeriser, Integeriser};
@@ -10,9 +10,9 @@ use util::tree::GornTree;
 /// upside-down tree with a designated position (the *stack pointer*) and *nodes* of type `A`.
 #[derive(Clone, Debug)]
 pub struct TreeStack<A> {
-    parent: Option<(usize, Rc<TreeStack<A>>)>,
+    parent: Option<(usize, Arc<TreeStack<A>>)>,
     value: A,
-    children: Vec<Option<Rc<TreeStack<A>>>>,
+    children: Vec<Option<Arc<TreeStack<A>>>>,
 }
 
 impl<A> TreeStack<A> {
@@ -27,10 +27,10 @@ impl<A> TreeStack<A> {
     {
         let new_value = f(&self.value);
         let new_parent = match self.parent {
-            Some((i, ref p)) => Some((i, Rc::new(p.map(f)))),
+            Some((i, ref p)) => Some((i, Arc::new(p.map(f)))),
             None => None,
         };
-        let new_children = self.children.iter().map(|o| o.clone().map(|v| Rc::new(v.map(f)))).collect();
+        let new_children = self.children.iter().map(|o| o.clone().map(|v| Arc::new(v.map(f)))).collect();
         TreeStack { parent: new_parent, value: new_value, children: new_children }
     }
 
@@ -62,7 +62,7 @@ impl<A> TreeStack<A> {
         if self.children[n].is_none() {
             Ok(TreeStack { value: a,
                            children: Vec::new(),
-                           parent: Some((n, Rc::new(self))) })
+                           parent: Some((n, Arc::new(self))) })
         } else {
             Err(self)
         }
@@ -115,7 +115,7 @@ impl<A: Clone> TreeStack<A> {
         } {
             Some(ref tn) => Ok(TreeStack { value: tn.value.clone(),
                                            children: tn.children.clone(),
-                                           parent: Some((n, Rc::new(self))) }),
+                                           parent: Some((n, Arc::new(self))) }),
             _ => Err(self),
         }
     }
@@ -126,7 +126,7 @@ impl<A: Clone> TreeStack<A> {
         match self.parent.take() {
             Some((n, pn)) => {
                 let mut new_pch = pn.children.clone();
-                new_pch[n] = Some(Rc::new(self));
+                new_pch[n] = Some(Arc::new(self));
                 Ok(TreeStack { value: pn.value.clone(),
                                children: new_pch,
                                parent: pn.parent.clone() })
@@ -184,7 +184,7 @@ impl<A: Clone + Eq + Hash> Integerisable1 for TreeStack<A> {
 
 impl<A: PartialEq> PartialEq for TreeStack<A> {
     fn eq(&self, other: &Self) -> bool {
-        let comp = |p1, p2| Rc::ptr_eq(p1, p2) || p1 == p2;
+        let comp = |p1, p2| Arc::ptr_eq(p1, p2) || p1 == p2;
         self.value == other.value
             && match (&self.parent, &other.parent) {
                 (&Some((i1, ref p1)), &Some((i2, ref p2))) => i1 == i2 && comp(p1, p2),
@@ -27,7 +27,8 @@ pub enum TreeStackInstruction<A> {
 
 
 impl<A> TreeStackInstruction<A> {
-    fn map<F, B>(&self, mut f: F) -> TreeStackInstruction<B>
+    /// Applies a function `FnMut(&A) -> B` to every value in the `TreeStackInstruction<A>`.
+    pub fn map<F, B>(&self, mut f: F) -> TreeStackInstruction<B>
         where F: FnMut(&A) -> B
     {
         match *self {
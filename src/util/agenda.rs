@@ -1,4 +1,6 @@
-use std::collections::{BinaryHeap, BTreeMap};
+use std::collections::{BinaryHeap, BTreeMap, HashMap};
+use std::hash::Hash;
+use std::ops::Mul;
 use std::vec::Vec;
 
 pub trait Agenda {
@@ -8,6 +10,7 @@ pub trait Agenda {
     fn dequeue(&mut self) -> Option<Self::Item>;
     fn peek_next(&self) -> Option<&Self::Item>;
     fn is_empty(&self) -> bool;
+    fn size(&self) -> usize;
 }
 
 pub trait Weighted {
@@ -16,6 +19,13 @@ pub trait Weighted {
     fn get_weight(&self) -> Self::Weight;
 }
 
+/// Estimates the weight that is still collected from an item until it is accepted.
+pub trait Heuristic<I> {
+    type Weight;
+
+    fn estimate(&self, item: &I) -> Self::Weight;
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct BoundedPriorityQueue<P, I> {
     data: BTreeMap<P, Vec<I>>, // The values should always be non-empty.
@@ -84,6 +94,10 @@ impl<P: Ord + Clone, I: Weighted<Weight=P>> Agenda for BoundedPriorityQueue<P, I
    fn is_empty(&self) -> bool {
         self.size == 0
     }
+
+    fn size(&self) -> usize {
+        self.size
+    }
 }
 
 impl<P: Ord, I> BoundedPriorityQueue<P, I> {
@@ -152,6 +166,10 @@ impl<I: Ord> Agenda for BinaryHeap<I> {
     fn is_empty(&self) -> bool {
         self.is_empty()
     }
+
+    fn size(&self) -> usize {
+        self.len()
+    }
 }
 
 impl<I> Agenda for Vec<I> {
@@ -173,8 +191,264 @@ impl<I> Agenda for Vec<I> {
     fn is_empty(&self) -> bool {
         self.is_empty()
     }
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+
+/// Agenda that orders its items by their weight times the estimate of a `Heuristic`, i.e. it
+/// performs an A* search.
+/// If the heuristic is admissible (it never estimates less than the actual weight that is still
+/// collected), then the items are accepted in the same order as with a `BinaryHeap`.
+pub struct AStarAgenda<W, I, H> {
+    heap: BinaryHeap<(W, I)>,
+    heuristic: H,
+}
+
+impl<W: Ord, I: Ord, H> AStarAgenda<W, I, H> {
+    pub fn new(heuristic: H) -> Self {
+        AStarAgenda { heap: BinaryHeap::new(), heuristic }
+    }
+}
+
+impl<W, I, H> Agenda for AStarAgenda<W, I, H>
+    where W: Mul<Output=W> + Ord,
+          I: Ord + Weighted<Weight=W>,
+          H: Heuristic<I, Weight=W>,
+{
+    type Item = I;
+
+    fn enqueue(&mut self, item: I) -> Option<I> {
+        let priority = item.get_weight() * self.heuristic.estimate(&item);
+        self.heap.push((priority, item));
+        None
+    }
+
+    fn dequeue(&mut self) -> Option<I> {
+        self.heap.pop().map(|(_, item)| item)
+    }
+
+    fn peek_next(&self) -> Option<&I> {
+        self.heap.peek().map(|&(_, ref item)| item)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    fn size(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+/// Agenda that rejects items whose weight is less than `threshold` times the greatest weight of
+/// all items with the same key that were enqueued so far, e.g. of all items that have read the
+/// same prefix of the word.
+/// The weights of items only decrease while they are processed, hence items with different keys
+/// are not compared.
+pub struct ThresholdAgenda<'a, K, P, I> {
+    heap: BinaryHeap<I>,
+    threshold: P,
+    best: HashMap<K, P>,
+    key: Box<Fn(&I) -> K + 'a>,
+}
+
+impl<'a, K: Eq + Hash, P, I: Ord> ThresholdAgenda<'a, K, P, I> {
+    pub fn new(threshold: P, key: Box<Fn(&I) -> K + 'a>) -> Self {
+        ThresholdAgenda { heap: BinaryHeap::new(), threshold, best: HashMap::new(), key }
+    }
+}
+
+impl<'a, K, P, I> Agenda for ThresholdAgenda<'a, K, P, I>
+    where K: Eq + Hash,
+          P: Clone + Mul<Output=P> + Ord,
+          I: Ord + Weighted<Weight=P>,
+{
+    type Item = I;
+
+    fn enqueue(&mut self, item: I) -> Option<I> {
+        let weight = item.get_weight();
+        let key = (self.key)(&item);
+        let (prune, improves) = match self.best.get(&key) {
+            Some(best) => (weight < best.clone() * self.threshold.clone(), weight > *best),
+            None => (false, true),
+        };
+        if prune {
+            Some(item)
+        } else {
+            if improves {
+                self.best.insert(key, weight);
+            }
+            self.heap.push(item);
+            None
+        }
+    }
+
+    fn dequeue(&mut self) -> Option<I> {
+        self.heap.pop()
+    }
+
+    fn peek_next(&self) -> Option<&I> {
+        self.heap.peek()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    fn size(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+/// Agenda that partitions its items by a key and keeps at most `capacity` items (those with the
+/// greatest weights) per key.
+/// Items are dequeued in the order of descending weight, regardless of their key.
+pub struct KeyedBeamAgenda<'a, K, P, I> {
+    beams: Vec<Beam<P, I>>,
+    beam_index: HashMap<K, usize>,
+    /// the greatest weight of each non-empty beam, with the index of the beam; entries whose
+    /// beam has changed since are skipped when they come to the top
+    best: BinaryHeap<(P, usize)>,
+    capacity: usize,
+    size: usize,
+    key: Box<Fn(&I) -> K + 'a>,
+}
+
+struct Beam<P, I> {
+    size: usize,
+    items: BTreeMap<P, Vec<I>>,
+}
+
+impl<P: Ord, I> Beam<P, I> {
+    fn best(&self) -> Option<(&P, &I)> {
+        self.items.iter().next_back().and_then(|(p, is)| is.last().map(|i| (p, i)))
+    }
+
+    fn insert(&mut self, priority: P, item: I) {
+        self.items.entry(priority).or_insert_with(Vec::new).push(item);
+        self.size += 1;
+    }
+
+    fn remove(&mut self, priority: &P) -> Option<I> {
+        let (item, is_empty) = match self.items.get_mut(priority) {
+            Some(is) => (is.pop(), is.is_empty()),
+            None => (None, false),
+        };
+        if is_empty {
+            self.items.remove(priority);
+        }
+        if item.is_some() {
+            self.size -= 1;
+        }
+        item
+    }
 }
 
+impl<'a, K: Eq + Hash, P: Ord, I> KeyedBeamAgenda<'a, K, P, I> {
+    pub fn new(capacity: usize, key: Box<Fn(&I) -> K + 'a>) -> Self {
+        assert!(capacity > 0);
+        KeyedBeamAgenda {
+            beams: Vec::new(),
+            beam_index: HashMap::new(),
+            best: BinaryHeap::new(),
+            capacity,
+            size: 0,
+            key,
+        }
+    }
+}
+
+impl<'a, K, P, I> KeyedBeamAgenda<'a, K, P, I>
+    where P: Clone + Ord,
+{
+    fn is_current(&self, priority: &P, beam: usize) -> bool {
+        self.beams[beam].best().map_or(false, |(p, _)| p == priority)
+    }
+
+    /// Removes the outdated entries from the top of `best`, such that it refers to the best item.
+    fn discard_outdated(&mut self) {
+        loop {
+            let current = match self.best.peek() {
+                Some(&(ref priority, beam)) => self.is_current(priority, beam),
+                None => return,
+            };
+            if current {
+                return;
+            }
+            self.best.pop();
+        }
+    }
+}
+
+impl<'a, K, P, I> Agenda for KeyedBeamAgenda<'a, K, P, I>
+    where K: Eq + Hash,
+          P: Clone + Ord,
+          I: Weighted<Weight=P>,
+{
+    type Item = I;
+
+    fn enqueue(&mut self, item: I) -> Option<I> {
+        let key = (self.key)(&item);
+        let priority = item.get_weight();
+        let beams = &mut self.beams;
+        let index = *self.beam_index.entry(key).or_insert_with(|| {
+            beams.push(Beam { size: 0, items: BTreeMap::new() });
+            beams.len() - 1
+        });
+
+        let improves = self.beams[index].best().map_or(true, |(p, _)| priority > *p);
+        let result = if self.beams[index].size < self.capacity {
+            self.beams[index].insert(priority.clone(), item);
+            self.size += 1;
+            None
+        } else {
+            let worst = self.beams[index].items.keys().next().expect("[ERROR] a full beam should not be empty.").clone();
+            if priority > worst {
+                self.beams[index].insert(priority.clone(), item);
+                self.beams[index].remove(&worst)
+            } else {
+                return Some(item);
+            }
+        };
+
+        if improves {
+            self.best.push((priority, index));
+        }
+        self.discard_outdated();
+        result
+    }
+
+    fn dequeue(&mut self) -> Option<I> {
+        let (priority, index) = match self.best.pop() {
+            Some(entry) => entry,
+            None => return None,
+        };
+        let item = self.beams[index].remove(&priority);
+        self.size -= 1;
+
+        let next_best = self.beams[index].best().map(|(p, _)| p.clone());
+        if let Some(next_priority) = next_best {
+            self.best.push((next_priority, index));
+        }
+        self.discard_outdated();
+        item
+    }
+
+    fn peek_next(&self) -> Option<&I> {
+        self.best.peek().and_then(|&(_, index)| self.beams[index].best()).map(|(_, i)| i)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
 
 #[test]
 fn test_bounded_priority_queue() {
@@ -232,3 +506,91 @@ fn test_bounded_priority_queue() {
     assert_eq!(q.dequeue(), Some('f'));
     assert_eq!(q.dequeue(), None);
 }
+
+#[test]
+fn test_a_star_agenda() {
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Node(u32, u32);
+
+    impl Weighted for Node {
+        type Weight = u32;
+
+        fn get_weight(&self) -> u32 {
+            self.0
+        }
+    }
+
+    struct Estimate;
+
+    impl Heuristic<Node> for Estimate {
+        type Weight = u32;
+
+        fn estimate(&self, node: &Node) -> u32 {
+            node.1
+        }
+    }
+
+    let mut q = AStarAgenda::new(Estimate);
+
+    assert_eq!(q.enqueue(Node(5, 1)), None);
+    assert_eq!(q.enqueue(Node(2, 4)), None);
+    assert_eq!(q.enqueue(Node(3, 2)), None);
+    assert_eq!(q.size(), 3);
+    assert_eq!(q.peek_next(), Some(&Node(2, 4)));
+
+    assert_eq!(q.dequeue(), Some(Node(2, 4)));
+    assert_eq!(q.dequeue(), Some(Node(3, 2)));
+    assert_eq!(q.dequeue(), Some(Node(5, 1)));
+    assert!(q.is_empty());
+}
+
+#[test]
+fn test_threshold_agenda() {
+    impl Weighted for u32 {
+        type Weight = Self;
+
+        fn get_weight(&self) -> Self::Weight {
+            *self
+        }
+    }
+
+    // items with the same parity compete
+    let mut q = ThresholdAgenda::new(1, Box::new(|n: &u32| n % 2));
+
+    assert_eq!(q.enqueue(7), None);
+    assert_eq!(q.enqueue(4), None);
+    assert_eq!(q.enqueue(3), Some(3));
+    assert_eq!(q.enqueue(10), None);
+    assert_eq!(q.enqueue(6), Some(6));
+    assert_eq!(q.size(), 3);
+
+    assert_eq!(q.dequeue(), Some(10));
+    assert_eq!(q.dequeue(), Some(7));
+
+    // the threshold still refers to 10, although only 4 is left
+    assert_eq!(q.enqueue(8), Some(8));
+    assert_eq!(q.enqueue(5), Some(5));
+    assert_eq!(q.dequeue(), Some(4));
+    assert!(q.is_empty());
+}
+
+#[test]
+fn test_keyed_beam_agenda() {
+    let mut q = KeyedBeamAgenda::new(2, Box::new(|c: &char| c.is_uppercase()));
+
+    assert_eq!(q.enqueue('a'), None);
+    assert_eq!(q.enqueue('b'), None);
+    assert_eq!(q.enqueue('c'), Some('a'));
+    assert_eq!(q.enqueue('A'), None);
+    assert_eq!(q.enqueue('B'), None);
+    assert_eq!(q.enqueue('C'), Some('A'));
+    assert_eq!(q.enqueue('D'), Some('B'));
+    assert_eq!(q.size(), 4);
+    assert_eq!(q.peek_next(), Some(&'c'));
+
+    assert_eq!(q.dequeue(), Some('c'));
+    assert_eq!(q.dequeue(), Some('b'));
+    assert_eq!(q.dequeue(), Some('D'));
+    assert_eq!(q.dequeue(), Some('C'));
+    assert_eq!(q.dequeue(), None);
+}
@@ -3,3 +3,4 @@ pub mod integerisable;
 pub mod parsing;
 pub mod push_down;
 pub mod tree;
+pub mod unknown_words;
@@ -35,6 +35,46 @@ pub fn parse_token<A>(input: &[u8])
     )
 }
 
+/// Returns the token `s` in a form that is read as `s` by `parse_token`, i.e. delimited by `'"'`
+/// if `s` is empty, starts with `'%'` (which would start a comment), or contains whitespace or
+/// one of the symbols `'"'`, `'-'`, `'→'`, `','`, `';'`, `'('`, `')'`, `'['`, `']'`, `'*'`.
+/// Since `parse_token` does not unescape, a token that is not `is_writable_token` can not be
+/// written in such a form; it is returned as it is.
+pub fn quote_token(s: &str) -> String {
+    if needs_quotes(s) && is_quotable(s) {
+        format!("\"{}\"", s)
+    } else {
+        s.to_string()
+    }
+}
+
+/// Checks whether `quote_token(s)` is read as `s` by `parse_token`, i.e. whether `s` does not
+/// need quotes or each `'"'` in `s` is escaped by `'\\'` and `s` does not end with an escaping
+/// `'\\'`.
+pub fn is_writable_token(s: &str) -> bool {
+    !needs_quotes(s) || is_quotable(s)
+}
+
+fn needs_quotes(s: &str) -> bool {
+    s.is_empty()
+        || s.starts_with('%')
+        || s.chars().any(|c| c.is_whitespace() || "\"-→,;()[]*".contains(c))
+}
+
+fn is_quotable(s: &str) -> bool {
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return false,
+            '\\' => if chars.next().is_none() {
+                return false;
+            },
+            _ => (),
+        }
+    }
+    true
+}
+
 /// Parses the `input` into a `Vec<A>` given an `inner_parser` for type `A`, an `opening` delimiter, a `closing` delimiter, and a `separator`.
 /// The `inner_parser` must not consume the `separator`s or the `closing` delimiter of the given `input`.
 pub fn parse_vec<'a, A, P>(input: &'a [u8], inner_parser: P, opening: &str, closing: &str, separator: &str)
@@ -109,6 +149,25 @@ pub fn initial_rule_grammar_from_str<I, R>(s: &str)
 pub mod tests {
     use super::*;
 
+    #[test]
+    fn test_quote_token() {
+        for token in vec!["NP", "NP-SBJ", "a b", "[A]", "%", "*"] {
+            let input = format!("{},", quote_token(token));
+            assert_eq!((&b","[..], String::from(token)), parse_token(input.as_bytes()).unwrap());
+        }
+        assert_eq!("NP", quote_token("NP"));
+        assert_eq!("\"NP-SBJ\"", quote_token("NP-SBJ"));
+
+        for token in vec!["a\\\"b", "a\\\"b-c", "a\\\\", "a\\"] {
+            assert!(is_writable_token(token));
+            let input = format!("{},", quote_token(token));
+            assert_eq!((&b","[..], String::from(token)), parse_token(input.as_bytes()).unwrap());
+        }
+        for token in vec!["a\"b", "a-\\", "a \\\\\\"] {
+            assert!(!is_writable_token(token));
+        }
+    }
+
     #[test]
     fn test_parse_token_legal_input() {
         let legal_inputs = vec![
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::collections::btree_map;
+use std::fmt;
 
 /// A tree map where each node has a _Gorn address_, i.e. a sequence of integers that describes the
 /// path one has to follow to get from the root to the particular node.
@@ -68,6 +69,298 @@ impl<V> GornTree<V> {
     pub fn is_empty(&self) -> bool {
         self.map.is_empty()
     }
+
+    /// Returns the positions `i` (in ascending order) such that `address` followed by `i` is a
+    /// node of the tree.
+    pub fn child_positions(&self, address: &[usize]) -> Vec<usize> {
+        self.map
+            .keys()
+            .filter(|k| k.len() == address.len() + 1 && k.starts_with(address))
+            .map(|k| k[address.len()])
+            .collect()
+    }
+}
+
+impl<V: fmt::Display> GornTree<V> {
+    /// Writes the tree in bracket notation, i.e. every inner node as `(label child_1 … child_k)`
+    /// and every leaf as `label`.
+    /// A constituent without children (e.g. one that derives the empty word) is represented by a
+    /// node whose only child is a leaf with an empty label, it is written as `(label )`.
+    pub fn to_bracket(&self) -> String {
+        let mut buffer = String::new();
+        if self.contains_key(&Vec::new()) {
+            self.write_node(&mut buffer, Vec::new(), " ", "(", ")", false);
+        }
+        buffer
+    }
+
+    /// Writes the tree in term notation, i.e. every inner node as `label(child_1, …, child_k)`
+    /// and every leaf as `label`.
+    pub fn to_term_string(&self) -> String {
+        let mut buffer = String::new();
+        if self.contains_key(&Vec::new()) {
+            self.write_node(&mut buffer, Vec::new(), ", ", "(", ")", true);
+        }
+        buffer
+    }
+
+    fn write_node(&self,
+                  buffer: &mut String,
+                  address: Vec<usize>,
+                  separator: &str,
+                  open: &str,
+                  close: &str,
+                  label_first: bool)
+    {
+        let label = self.map.get(&address).map(|v| v.to_string()).unwrap_or_default();
+        let children = self.child_positions(&address);
+        if children.is_empty() {
+            buffer.push_str(&label);
+            return;
+        }
+
+        if label_first {
+            buffer.push_str(&label);
+            buffer.push_str(open);
+        } else {
+            buffer.push_str(open);
+            buffer.push_str(&label);
+            buffer.push_str(separator);
+        }
+        for (n, i) in children.into_iter().enumerate() {
+            if n > 0 {
+                buffer.push_str(separator);
+            }
+            let mut child = address.clone();
+            child.push(i);
+            self.write_node(buffer, child, separator, open, close, label_first);
+        }
+        buffer.push_str(close);
+    }
+}
+
+impl GornTree<String> {
+    /// Reads a single tree in (Penn-style) bracket notation, cf. `to_bracket`.
+    ///
+    /// ```
+    /// use rustomata::util::tree::GornTree;
+    ///
+    /// let tree = GornTree::from_bracket("( (S (NP (DT the) (NN dog)) (VP barks)) )").unwrap();
+    /// assert_eq!("(S (NP (DT the) (NN dog)) (VP barks))", tree.to_bracket());
+    ///
+    /// let tree = GornTree::from_bracket("(S (A ) b)").unwrap();
+    /// assert_eq!(Some(&String::new()), tree.get(&vec![0, 0]));
+    /// assert_eq!("(S (A ) b)", tree.to_bracket());
+    /// ```
+    pub fn from_bracket(s: &str) -> Result<GornTree<String>, String> {
+        let mut trees = read_bracket_corpus(s)?;
+        match trees.len() {
+            1 => Ok(trees.remove(0)),
+            n => Err(format!("Expected exactly one tree, but found {}.", n)),
+        }
+    }
+
+    /// Returns whether the node at `address` is a trace (i.e. labelled with `-NONE-`) or an inner
+    /// node that only dominates traces.
+    pub fn is_trace(&self, address: &[usize]) -> bool {
+        if self.map.get(address).map_or(false, |label| label == "-NONE-") {
+            return true;
+        }
+        let children = self.child_positions(address);
+        !children.is_empty() && children.into_iter().all(|i| {
+            let mut child = address.to_vec();
+            child.push(i);
+            self.is_trace(&child)
+        })
+    }
+}
+
+/// A child of a constituent in `GornTree::from_constituents`.
+pub enum ConstituentChild<D> {
+    /// A leaf with the given label, e.g. a terminal symbol.
+    Leaf(String),
+    /// A constituent that is expanded recursively.
+    Constituent(D),
+}
+
+impl GornTree<String> {
+    /// Builds a constituent tree top-down from the `root` constituent.
+    /// `expand` returns the label and the children of a constituent, or `None` if the constituent
+    /// is omitted from the tree; it is called in pre-order, i.e. from left to right.
+    /// A constituent without children is given an empty leaf, cf. `to_bracket`.
+    ///
+    /// ```
+    /// use rustomata::util::tree::{ConstituentChild, GornTree};
+    ///
+    /// let tree = GornTree::from_constituents(2, |&n| if n > 0 {
+    ///     Some((format!("N{}", n), vec![ConstituentChild::Leaf("x".to_string()),
+    ///                                   ConstituentChild::Constituent(n - 1)]))
+    /// } else {
+    ///     Some(("N0".to_string(), Vec::new()))
+    /// });
+    /// assert_eq!("(N2 x (N1 x (N0 )))", tree.to_bracket());
+    /// ```
+    pub fn from_constituents<D, F>(root: D, mut expand: F) -> GornTree<String>
+        where F: FnMut(&D) -> Option<(String, Vec<ConstituentChild<D>>)>,
+    {
+        let mut tree = GornTree::new();
+        insert_constituent(&mut tree, Vec::new(), &root, &mut expand);
+        tree
+    }
+}
+
+/// Inserts the constituent at `address` and returns whether it was inserted.
+fn insert_constituent<D, F>(tree: &mut GornTree<String>,
+                            address: Vec<usize>,
+                            constituent: &D,
+                            expand: &mut F)
+                            -> bool
+    where F: FnMut(&D) -> Option<(String, Vec<ConstituentChild<D>>)>,
+{
+    let (label, children) = match expand(constituent) {
+        Some(expansion) => expansion,
+        None => return false,
+    };
+
+    let mut position = 0;
+    for child in children {
+        let mut child_address = address.clone();
+        child_address.push(position);
+        let inserted = match child {
+            ConstituentChild::Leaf(leaf) => {
+                tree.insert(child_address, leaf);
+                true
+            },
+            ConstituentChild::Constituent(d) => insert_constituent(tree, child_address, &d, expand),
+        };
+        if inserted {
+            position += 1;
+        }
+    }
+    if position == 0 {
+        let mut child_address = address.clone();
+        child_address.push(0);
+        tree.insert(child_address, String::new());
+    }
+
+    tree.insert(address, label);
+    true
+}
+
+/// Node of a tree in bracket notation while it is read.
+struct BracketNode {
+    label: String,
+    children: Vec<BracketNode>,
+}
+
+impl BracketNode {
+    fn insert_into(self, tree: &mut GornTree<String>, address: Vec<usize>) {
+        for (i, child) in self.children.into_iter().enumerate() {
+            let mut child_address = address.clone();
+            child_address.push(i);
+            child.insert_into(tree, child_address);
+        }
+        tree.insert(address, self.label);
+    }
+}
+
+/// Reads all trees in (Penn-style) bracket notation from `s`, e.g. the contents of a treebank
+/// file.
+/// The outermost bracket of a tree may be unlabelled (as in the Penn Treebank), it is removed if
+/// it has exactly one child.
+pub fn read_bracket_corpus(s: &str) -> Result<Vec<GornTree<String>>, String> {
+    let tokens = tokenise_bracket(s);
+    let mut position = 0;
+    let mut trees = Vec::new();
+
+    while position < tokens.len() {
+        let mut node = read_bracket_node(&tokens, &mut position)?;
+        if node.label.is_empty() && node.children.len() == 1 {
+            node = node.children.remove(0);
+        }
+        let mut tree = GornTree::new();
+        node.insert_into(&mut tree, Vec::new());
+        trees.push(tree);
+    }
+
+    Ok(trees)
+}
+
+fn tokenise_bracket(s: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+
+    for c in s.chars() {
+        if c == '(' || c == ')' || c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(current.clone());
+                current.clear();
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn read_bracket_node(tokens: &[String], position: &mut usize) -> Result<BracketNode, String> {
+    match tokens.get(*position).map(|t| t.as_str()) {
+        Some("(") => *position += 1,
+        Some(token) => return Err(format!("Expected '(' but found '{}'.", token)),
+        None => return Err("Unexpected end of input.".to_string()),
+    }
+
+    let label = match tokens.get(*position).map(|t| t.as_str()) {
+        Some("(") | Some(")") => String::new(),
+        Some(token) => {
+            *position += 1;
+            token.to_string()
+        },
+        None => return Err("Unexpected end of input.".to_string()),
+    };
+
+    let mut children = Vec::new();
+    loop {
+        match tokens.get(*position).map(|t| t.as_str()) {
+            Some(")") => {
+                *position += 1;
+                break;
+            },
+            Some("(") => children.push(read_bracket_node(tokens, position)?),
+            Some(token) => {
+                *position += 1;
+                children.push(BracketNode { label: token.to_string(), children: Vec::new() });
+            },
+            None => return Err(format!("Missing ')' for node '{}'.", label)),
+        }
+    }
+
+    // an empty constituent, cf. `GornTree::to_bracket`
+    if children.is_empty() && !label.is_empty() {
+        children.push(BracketNode { label: String::new(), children: Vec::new() });
+    }
+
+    Ok(BracketNode { label, children })
+}
+
+/// Prints one node per line, indented by its depth and prefixed with its position.
+impl<V: fmt::Display> fmt::Display for GornTree<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (address, value) in &self.map {
+            match address.last() {
+                Some(i) => writeln!(f, "{}{}: {}", "  ".repeat(address.len()), i, value)?,
+                None => writeln!(f, "{}", value)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<V> IntoIterator for GornTree<V> {
@@ -102,3 +395,36 @@ impl<V: PartialEq> PartialEq for GornTree<V> {
         self.map.eq(&other.map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notations() {
+        let mut tree = GornTree::new();
+        tree.insert(vec![], "S");
+        tree.insert(vec![0], "A");
+        tree.insert(vec![0, 0], "a");
+        tree.insert(vec![1], "b");
+
+        assert_eq!(vec![0, 1], tree.child_positions(&[]));
+        assert_eq!("(S (A a) b)", tree.to_bracket());
+        assert_eq!("S(A(a), b)", tree.to_term_string());
+        assert_eq!("S\n  0: A\n    0: a\n  1: b\n", tree.to_string());
+    }
+
+    #[test]
+    fn test_read_bracket_corpus() {
+        let corpus = "( (S (A a) b) )\n(S (A (B a) (C c)))";
+        let trees = read_bracket_corpus(corpus).unwrap();
+
+        assert_eq!(2, trees.len());
+        assert_eq!("(S (A a) b)", trees[0].to_bracket());
+        assert_eq!(vec![0, 1], trees[1].child_positions(&[0]));
+        assert_eq!(Some(&"c".to_string()), trees[1].get(&vec![0, 1, 0]));
+
+        assert!(GornTree::from_bracket("(S (A a) b").is_err());
+        assert!(GornTree::from_bracket("(S a) (S b)").is_err());
+    }
+}
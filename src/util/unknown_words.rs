@@ -0,0 +1,172 @@
+use log_domain::LogDomain;
+use num_traits::Zero;
+use std::collections::BTreeMap;
+use std::ops::{AddAssign, Mul};
+
+/// Terminal symbol for words whose signature is not known either.
+pub const UNKNOWN_WORD: &'static str = "UNK";
+
+/// Returns the signature class of `word`, i.e. `UNK` followed by markers for capitalisation
+/// (`-C` for a capitalised, `-c` for an all-lowercase word), digits (`-NUM`), hyphens (`-DASH`),
+/// and the last two characters of the (lowercased) word if it has more than three characters.
+///
+/// ```
+/// use rustomata::util::unknown_words::signature;
+///
+/// assert_eq!("UNK-C-ng", signature("Running"));
+/// assert_eq!("UNK-c-DASH-ed", signature("well-dressed"));
+/// assert_eq!("UNK-NUM", signature("1984"));
+/// ```
+pub fn signature(word: &str) -> String {
+    let mut signature = UNKNOWN_WORD.to_string();
+
+    if word.chars().next().map_or(false, char::is_uppercase) {
+        signature.push_str("-C");
+    } else if word.chars().any(char::is_lowercase) && !word.chars().any(char::is_uppercase) {
+        signature.push_str("-c");
+    }
+    if word.chars().any(|c| c.is_numeric()) {
+        signature.push_str("-NUM");
+    }
+    if word.contains('-') {
+        signature.push_str("-DASH");
+    }
+
+    let lowercase: Vec<char> = word.to_lowercase().chars().collect();
+    if lowercase.len() > 3 && lowercase[lowercase.len() - 2..].iter().all(|c| c.is_alphabetic()) {
+        signature.push('-');
+        signature.extend(&lowercase[lowercase.len() - 2..]);
+    }
+
+    signature
+}
+
+/// Replaces every token of `word` that is not known (according to `is_known`) by its
+/// `signature`, or by `UNKNOWN_WORD` if the signature is not known either.
+pub fn replace_unknown_words<F>(word: Vec<String>, is_known: F) -> Vec<String>
+    where F: Fn(&String) -> bool,
+{
+    word.into_iter()
+        .map(|token| replace_unknown_word(token, &is_known))
+        .collect()
+}
+
+/// Like `replace_unknown_words`, but for a single `token`, e.g. the label of a lattice edge.
+pub fn replace_unknown_word<F>(token: String, is_known: &F) -> String
+    where F: Fn(&String) -> bool,
+{
+    if is_known(&token) {
+        return token;
+    }
+    let class = signature(&token);
+    if is_known(&class) {
+        class
+    } else {
+        UNKNOWN_WORD.to_string()
+    }
+}
+
+/// Factors with which the weights of lexical rules are distributed among the word itself, its
+/// signature and `UNKNOWN_WORD`, cf. `smooth_lexicon`.
+/// The sum of the three factors should be one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LexicalSmoothing<W> {
+    pub known: W,
+    pub signature: W,
+    pub unknown: W,
+}
+
+impl LexicalSmoothing<LogDomain<f64>> {
+    /// Moves the share `lambda` of the weight of each word to its signature and to `UNKNOWN_WORD`
+    /// in equal parts, or returns an error if `lambda` is not between 0 and 1.
+    ///
+    /// ```
+    /// extern crate log_domain;
+    /// extern crate rustomata;
+    ///
+    /// use log_domain::LogDomain;
+    /// use rustomata::util::unknown_words::LexicalSmoothing;
+    ///
+    /// # fn main() {
+    /// let smoothing = LexicalSmoothing::from_factor(0.5).unwrap();
+    /// assert_eq!(LogDomain::new(0.25).unwrap(), smoothing.unknown);
+    /// assert!(LexicalSmoothing::from_factor(1.5).is_err());
+    /// # }
+    /// ```
+    pub fn from_factor(lambda: f64) -> Result<Self, String> {
+        if !(0.0 <= lambda && lambda <= 1.0) {
+            return Err(format!("{} is not a number between 0 and 1", lambda));
+        }
+        Ok(LexicalSmoothing {
+            known: LogDomain::new(1.0 - lambda).unwrap(),
+            signature: LogDomain::new(lambda / 2.0).unwrap(),
+            unknown: LogDomain::new(lambda / 2.0).unwrap(),
+        })
+    }
+}
+
+/// Checks that a command line argument is a factor between 0 and 1, e.g. for
+/// `LexicalSmoothing::from_factor`.
+pub fn validate_factor(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(factor) if 0.0 <= factor && factor <= 1.0 => Ok(()),
+        _ => Err(format!("\'{}\' is not a number between 0 and 1", value)),
+    }
+}
+
+/// Smooths a lexicon of (preterminal, word, weight) triples: every entry `(A, w, p)` keeps the
+/// weight `known · p` and contributes `signature · p` to `(A, signature(w))` and `unknown · p`
+/// to `(A, UNKNOWN_WORD)`.
+/// Hence the total weight of each preterminal is preserved if the factors sum to one.
+pub fn smooth_lexicon<N, W>(lexicon: &[(N, String, W)], smoothing: &LexicalSmoothing<W>)
+                            -> Vec<(N, String, W)>
+    where N: Clone + Ord,
+          W: AddAssign + Copy + Mul<Output=W> + Zero,
+{
+    let mut smoothed = BTreeMap::new();
+    for &(ref preterminal, ref word, weight) in lexicon {
+        let entries = vec![
+            (word.clone(), smoothing.known * weight),
+            (signature(word), smoothing.signature * weight),
+            (UNKNOWN_WORD.to_string(), smoothing.unknown * weight),
+        ];
+        for (w, p) in entries {
+            *smoothed.entry((preterminal.clone(), w)).or_insert_with(W::zero) += p;
+        }
+    }
+
+    smoothed.into_iter().map(|((preterminal, word), weight)| (preterminal, word, weight)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooth_lexicon() {
+        let lexicon = vec![
+            ('N', "dog".to_string(), 0.5),
+            ('N', "Dogs".to_string(), 0.5),
+        ];
+        let smoothing = LexicalSmoothing { known: 0.5, signature: 0.25, unknown: 0.25 };
+        let smoothed = smooth_lexicon(&lexicon, &smoothing);
+
+        assert_eq!(
+            vec![
+                ('N', "Dogs".to_string(), 0.25),
+                ('N', "UNK".to_string(), 0.25),
+                ('N', "UNK-C-gs".to_string(), 0.125),
+                ('N', "UNK-c".to_string(), 0.125),
+                ('N', "dog".to_string(), 0.25),
+            ],
+            smoothed
+        );
+
+        let known = vec!["dog".to_string(), "UNK-C-gs".to_string(), "UNK".to_string()];
+        let word = vec!["dog".to_string(), "Hogs".to_string(), "cat".to_string()];
+        assert_eq!(
+            vec!["dog".to_string(), "UNK-C-gs".to_string(), "UNK".to_string()],
+            replace_unknown_words(word, |t| known.contains(t))
+        );
+    }
+}
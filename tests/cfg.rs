@@ -3,17 +3,24 @@ extern crate num_traits;
 extern crate rustomata;
 
 use log_domain::LogDomain;
+use std::cell::RefCell;
 // TODO: Uncomment once PushDownAutomaton::FromStr has been implemented
 // use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
+use std::rc::Rc;
 
 use rustomata::approximation::ApproximationStrategy;
-use rustomata::approximation::equivalence_classes::EquivalenceRelation;
+use rustomata::approximation::equivalence_classes::{EquivalenceRelation, RelationHierarchy};
 use rustomata::approximation::relabel::RlbElement;
 use rustomata::cfg::*;
 use rustomata::push_down_automaton::*;
 use rustomata::recognisable::*;
+use rustomata::recognisable::coarse_to_fine::relabelling_recogniser;
+use rustomata::recognisable::automaton::{Automaton, BeamMode, next_terminals, recognise_beam_mode_bounded, recognise_beam_with_options, recognise_bounded, recognise_prefix, recognise_with_options};
+use rustomata::recognisable::lattice::{Lattice, LatticeRecognisable, recognise_lattice_beam_mode_bounded, recognise_lattice_bounded};
+use rustomata::util::tree::GornTree;
+use rustomata::util::unknown_words::{LexicalSmoothing, replace_unknown_words};
 // TODO: Uncomment once PushDownAutomaton::FromStr has been implemented
 // use rustomata::recognisable::automaton::Automaton;
 
@@ -74,6 +81,43 @@ fn test_relabel_pushdown_correctness() {
     }
 }
 
+#[test]
+fn test_relabel_hierarchy_coarse_to_fine() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example2.cfg"));
+    let hierarchy: RelationHierarchy<String, String> = "level:\n\
+                                                        N [A, B]\n\
+                                                        R *\n\
+                                                        level:\n\
+                                                        A [A]\n\
+                                                        B [B]\n\
+                                                        R *".parse().unwrap();
+    let mappings: Vec<Box<Fn(&PushState<String, String>) -> PushState<String, String>>> = hierarchy
+        .relabellings()
+        .into_iter()
+        .map(|rel| -> Box<Fn(&PushState<String, String>) -> PushState<String, String>> {
+            Box::new(move |ps: &PushState<_, _>| ps.map(|nt| rel.project(nt)))
+        })
+        .collect();
+    let recogniser = relabelling_recogniser(automaton.clone(), &mappings);
+
+    let inputs = vec![
+        "aab",
+        "bba",
+        "aaabb",
+        "aaa",
+        "bbb",
+        "aabaa",
+    ];
+
+    for input in inputs {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        assert_eq!(
+            automaton.recognise(word.clone()).next().is_some(),
+            recogniser.recognise(word).next().is_some()
+        );
+    }
+}
+
 #[test]
 fn test_cfg_from_str_correctness() {
     let rule_s0 = CFGRule {
@@ -192,3 +236,249 @@ fn test_cfg_recognise_illegal_terminal_symbols() {
         assert!(automaton.recognise(illegal_word).next().is_none());
     }
 }
+
+#[test]
+fn test_cfg_unknown_words() {
+    let grammar: CFG<String, String, LogDomain<f64>> = "initial: [S]\n\
+                                                        S → [Nt D, Nt N] # 1\n\
+                                                        D → [T the] # 1\n\
+                                                        N → [T dog] # 0.5\n\
+                                                        N → [T Dogs] # 0.5".parse().unwrap();
+    let word: Vec<String> = vec!["the".to_string(), "cat".to_string()];
+
+    let automaton = PushDownAutomaton::from(grammar.clone());
+    assert_eq!(None, automaton.terminal_to_int(&"cat".to_string()));
+    assert!(automaton.recognise(word.clone()).next().is_none());
+    assert!(next_terminals(&automaton, word.clone(), 10).is_empty());
+
+    let smoothing = LexicalSmoothing {
+        known: LogDomain::new(0.8).unwrap(),
+        signature: LogDomain::new(0.1).unwrap(),
+        unknown: LogDomain::new(0.1).unwrap(),
+    };
+    let automaton = PushDownAutomaton::from(smooth_lexical_rules(&grammar, &smoothing));
+    let word = replace_unknown_words(word, |t| automaton.terminal_to_int(t).is_some());
+    assert_eq!(vec!["the".to_string(), "UNK-c".to_string()], word);
+    assert!(automaton.recognise(word).next().is_some());
+}
+
+#[test]
+fn test_cfg_derivation() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+    let parse = automaton.recognise(
+        String::from("aabb").chars().map(|x| x.to_string()).collect()
+    ).next().unwrap();
+
+    let derivation = to_derivation(&parse.1);
+    assert_eq!(3, derivation.len());
+    assert_eq!(
+        &"S → [T a, Nt S, T b] # 0.4".parse::<CFGRule<String, String, LogDomain<f64>>>().unwrap(),
+        derivation.get(&vec![0]).unwrap()
+    );
+    assert_eq!(
+        &"S → [] # 0.6".parse::<CFGRule<String, String, LogDomain<f64>>>().unwrap(),
+        derivation.get(&vec![0, 0]).unwrap()
+    );
+    assert_eq!("(S a (S a (S ) b) b)", to_bracket(&derivation));
+    assert_eq!(derivation, from_bracket(&to_bracket(&derivation)).unwrap());
+}
+
+#[test]
+fn test_cfg_bracket_round_trip() {
+    let bracket = "(S (NP-SBJ-1 (DT the) (NN dog)) (VP (VBZ barks) (NP (-NONE- *T*-1))))";
+    let derivation: GornTree<CFGRule<String, String, LogDomain<f64>>> = from_bracket(bracket).unwrap();
+
+    assert_eq!(
+        "(S (NP-SBJ (DT the) (NN dog)) (VP (VBZ barks)))",
+        to_bracket(&derivation)
+    );
+    assert_eq!(derivation, from_bracket(&to_bracket(&derivation)).unwrap());
+    assert!(from_bracket::<LogDomain<f64>>("(S (NP the dog)").is_err());
+}
+
+#[test]
+fn test_example_cfg_split_lexicon() {
+    let (grammar, mut lexicon) = split_lexicon(&cfg_from_file("examples/example.cfg"));
+    lexicon.append("x a # 0.5".parse().unwrap());
+    let automaton = PushDownAutomaton::from(grammar);
+
+    let word: Vec<String> = vec!["x", "a", "b", "b"].into_iter().map(|x| x.to_string()).collect();
+    let parse = automaton.recognise_lattice(lexicon.to_lattice(&word)).next().unwrap();
+    assert_eq!(
+        "(S (a a) (S (a a) (S ) (b b)) (b b))",
+        to_bracket(&to_derivation(&parse.1))
+    );
+    assert_eq!(
+        "(S (a x) (S (a a) (S ) (b b)) (b b))",
+        to_bracket(&replace_terminals(&to_derivation(&parse.1), &word).unwrap())
+    );
+
+    let word: Vec<String> = vec!["a", "b", "b"].into_iter().map(|x| x.to_string()).collect();
+    assert!(automaton.recognise_lattice(lexicon.to_lattice(&word)).next().is_none());
+}
+
+#[test]
+fn test_cfg_recognise_lattice() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+
+    let mut lattice_file = File::open("examples/example.lattice").unwrap();
+    let mut lattice_string = String::new();
+    let _ = lattice_file.read_to_string(&mut lattice_string);
+    let lattice: Lattice<String, LogDomain<f64>> = lattice_string.parse().unwrap();
+
+    let parses: Vec<_> = automaton.recognise_lattice(lattice.clone()).collect();
+    assert_eq!(1, parses.len());
+    assert_eq!(4, (parses[0].0).node);
+
+    for mode in vec![BeamMode::Width(100), BeamMode::Position(100), BeamMode::StorageKey(100), BeamMode::Threshold(LogDomain::new(0.01).unwrap())] {
+        let beam_parses: Vec<_> = recognise_lattice_beam_mode_bounded(&automaton, mode, lattice.clone(), SearchOptions::default())
+            .map(|parse| parse.unwrap())
+            .collect();
+        assert_eq!(1, beam_parses.len());
+        assert_eq!(parses[0].0, beam_parses[0].0);
+    }
+
+    let options = SearchOptions {
+        limits: SearchLimits { max_items: Some(1), ..SearchLimits::default() },
+        ..SearchOptions::default()
+    };
+    assert_eq!(
+        Some(Err(GaveUp::ItemLimit)),
+        recognise_lattice_bounded(&automaton, lattice, options).next().map(|parse| parse.map(|_| ()))
+    );
+
+    let words: Vec<Vec<String>> = vec![
+        vec![],
+        vec!["a".to_string(), "b".to_string()],
+        vec!["a".to_string(), "a".to_string(), "b".to_string()],
+    ];
+    for word in words {
+        assert_eq!(
+            automaton.recognise(word.clone()).next().is_some(),
+            automaton.recognise_lattice(Lattice::from(word)).next().is_some()
+        );
+    }
+}
+
+#[test]
+fn test_cfg_prefix_recognition() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+    let inputs = vec![
+        ("", true, vec!["a"]),
+        ("aa", true, vec!["a", "b"]),
+        ("aab", true, vec!["b"]),
+        ("ab", true, vec![]),
+        ("abb", false, vec![]),
+        ("ba", false, vec![]),
+    ];
+
+    for (input, control_acceptance, control_terminals) in inputs {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        assert_eq!(
+            control_acceptance,
+            recognise_prefix(&automaton, word.clone()).next().is_some()
+        );
+
+        let mut terminals: Vec<_> = next_terminals(&automaton, word, 100)
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect();
+        terminals.sort();
+        assert_eq!(control_terminals, terminals);
+    }
+}
+
+#[test]
+fn test_cfg_search_statistics() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+    let word: Vec<_> = String::from("aabb").chars().map(|x| x.to_string()).collect();
+
+    let statistics = Rc::new(RefCell::new(SearchStatistics::default()));
+    let options = SearchOptions { statistics: Some(statistics.clone()), ..SearchOptions::default() };
+    assert!(recognise_with_options(&automaton, word.clone(), options).next().is_some());
+    {
+        let s = statistics.borrow();
+        assert!(s.dequeued > 0);
+        assert!(s.transitions_applied >= s.dequeued - 1);
+        assert!(s.agenda_peak > 0);
+        assert_eq!(0, s.pruned);
+    }
+
+    let beam_statistics = Rc::new(RefCell::new(SearchStatistics::default()));
+    let beam_options = SearchOptions { statistics: Some(beam_statistics.clone()), ..SearchOptions::default() };
+    let _ = recognise_beam_with_options(&automaton, 1, word, beam_options).next();
+    assert!(beam_statistics.borrow().agenda_peak <= 1);
+}
+
+#[test]
+fn test_cfg_search_limits() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+    let word: Vec<_> = String::from("aabb").chars().map(|x| x.to_string()).collect();
+
+    let options = SearchOptions {
+        limits: SearchLimits { max_items: Some(1), ..SearchLimits::default() },
+        ..SearchOptions::default()
+    };
+    let results: Vec<_> = recognise_bounded(&automaton, word.clone(), options).collect();
+    assert_eq!(1, results.len());
+    assert_eq!(Some(&GaveUp::ItemLimit), results[0].as_ref().err());
+
+    let options = SearchOptions {
+        limits: SearchLimits { max_items: Some(1000), ..SearchLimits::default() },
+        ..SearchOptions::default()
+    };
+    let mut results = recognise_bounded(&automaton, word, options);
+    assert!(results.next().unwrap().is_ok());
+}
+
+#[test]
+fn test_cfg_closed_set() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+    let word: Vec<_> = String::from("aabb").chars().map(|x| x.to_string()).collect();
+
+    let best = recognise_with_options(&automaton, word.clone(), SearchOptions::default()).next().unwrap();
+
+    let options = SearchOptions { closed_set: Some(1), ..SearchOptions::default() };
+    let mut parses = recognise_with_options(&automaton, word, options);
+    assert_eq!(best.0.weight, parses.next().unwrap().0.weight);
+    assert_eq!(None, parses.next());
+}
+
+#[test]
+fn test_cfg_astar() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+    let word: Vec<_> = String::from("aabb").chars().map(|x| x.to_string()).collect();
+
+    let best = automaton.recognise(word.clone()).next().unwrap();
+    let astar = automaton.recognise_astar(word, SearchOptions::default()).next().unwrap();
+    assert_eq!(best.0.weight, astar.0.weight);
+    assert_eq!(best.1, astar.1);
+
+    let empty = automaton.recognise_astar(Vec::new(), SearchOptions::default()).next().unwrap();
+    assert_eq!(LogDomain::new(0.6).unwrap(), empty.0.weight);
+}
+
+#[test]
+fn test_cfg_beam_modes() {
+    let automaton = PushDownAutomaton::from(cfg_from_file("examples/example.cfg"));
+
+    // the weight of the parse of the long word is less than the threshold
+    for input in vec!["aabb", "aaaaaaaaaabbbbbbbbbb"] {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        let best = automaton.recognise(word.clone()).next().unwrap();
+
+        let modes = vec![
+            BeamMode::Width(10),
+            BeamMode::Threshold(LogDomain::new(0.01).unwrap()),
+            BeamMode::Position(3),
+            BeamMode::StorageKey(3),
+        ];
+        for mode in modes {
+            let parse = recognise_beam_mode_bounded(&automaton, mode, word.clone(), SearchOptions::default())
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(best.0.weight, parse.0.weight);
+        }
+    }
+}
@@ -6,17 +6,26 @@ use log_domain::LogDomain;
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
-use std::rc::Rc;
+use std::sync::Arc;
 
-use rustomata::approximation::ApproximationStrategy;
+use rustomata::approximation::{ApproximationStrategy, FineRuns};
+use rustomata::approximation::compose::Compose;
 use rustomata::approximation::equivalence_classes::EquivalenceRelation;
-use rustomata::approximation::relabel::RlbElement;
+use rustomata::approximation::grammar::{restrict, split_components};
+use rustomata::approximation::relabel::{RlbElement, TreeStackRlbElement};
 use rustomata::approximation::tts::TTSElement;
 use rustomata::pmcfg::*;
 use rustomata::pmcfg::negra::to_negra;
+use rustomata::push_down_automaton::PushDownAutomaton;
 use rustomata::recognisable::*;
+use rustomata::recognisable::automaton::Automaton;
+use rustomata::recognisable::chart_pruning::ChartPruningRecogniser;
 use rustomata::recognisable::coarse_to_fine::CoarseToFineRecogniser;
+use rustomata::recognisable::incremental::IncrementalRecogniser;
+use rustomata::recognisable::lattice::LatticeRecognisable;
 use rustomata::tree_stack_automaton::*;
+use rustomata::util::push_down::Pushdown;
+use rustomata::util::tree::GornTree;
 
 fn pmcfg_from_file(grammar_file_path: &str) -> PMCFG<String, String, LogDomain<f64>>
 {
@@ -55,6 +64,67 @@ fn test_example_pmcfg_to_negra() {
     assert_eq!(negra_control_string, negra_string);
 }
 
+#[test]
+fn test_example_pmcfg_derivation() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let configuration = automaton.recognise(
+        String::from("aabccd").chars().map(|x| x.to_string()).collect()
+    ).next().unwrap().0;
+
+    let derivation = to_derivation(&configuration.storage);
+    assert_eq!(to_abstract_syntax_tree(configuration.storage.to_tree()), derivation);
+    assert_eq!("S", derivation.get(&vec![]).unwrap().head);
+    assert_eq!(2, derivation.get(&vec![0, 0]).unwrap().composition.composition.len());
+    assert_eq!(
+        "(S (A-1 a (A-2 a (A-3 ))) (B-4 b (B-5 )) (A=1 c (A=2 c (A=3 ))) (B=4 d (B=5 )))",
+        to_bracket(&derivation)
+    );
+
+    let tree = to_label_tree(&derivation);
+    let leaves: String = tree.keys()
+        .filter(|address| tree.child_positions(address).is_empty())
+        .map(|address| tree.get(address).unwrap().as_str())
+        .collect();
+    assert_eq!("aabccd", leaves);
+}
+
+#[test]
+fn test_example_pmcfg_bracket_round_trip() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let configuration = automaton.recognise(
+        String::from("aabccd").chars().map(|x| x.to_string()).collect()
+    ).next().unwrap().0;
+
+    let derivation = to_derivation(&configuration.storage);
+    assert_eq!(derivation, from_bracket(&to_bracket(&derivation)).unwrap());
+
+    let bracket = "(S (A-1 a (-NONE- *T*-2)) (B b) (A=1 c))";
+    let derivation: GornTree<PMCFGRule<String, String, LogDomain<f64>>> = from_bracket(bracket).unwrap();
+    assert_eq!("(S (A-1 a) (B b) (A=1 c))", to_bracket(&derivation));
+    assert!(from_bracket::<LogDomain<f64>>("(S (A-1 a) (B=1 c))").is_err());
+
+    // heads that look like co-indexed components are escaped
+    let mut derivation: GornTree<PMCFGRule<String, String, LogDomain<f64>>> = GornTree::new();
+    derivation.insert(vec![], "\"S-0\" → [[Var 0 0, Var 1 0, Var 0 1]] (\"A-1\", \"B=2\")".parse().unwrap());
+    derivation.insert(vec![0], "\"A-1\" → [[T a], [T c]] ()".parse().unwrap());
+    derivation.insert(vec![1], "\"B=2\" → [[T b]] ()".parse().unwrap());
+    assert_eq!("(S-0-0 (A-1-1 a) (B=2-0 b) (A-1=1 c))", to_bracket(&derivation));
+    assert_eq!(derivation, from_bracket(&to_bracket(&derivation)).unwrap());
+}
+
+#[test]
+fn test_example_pmcfg_split_lexicon() {
+    let (grammar, mut lexicon) = split_lexicon(&pmcfg_from_file("examples/example.pmcfg"));
+    lexicon.append("x a # 0.5".parse().unwrap());
+    let automaton = TreeStackAutomaton::from(grammar);
+
+    let word: Vec<String> = vec!["x", "a", "b", "c", "c", "d"].into_iter().map(|x| x.to_string()).collect();
+    assert!(automaton.recognise_lattice(lexicon.to_lattice(&word)).next().is_some());
+
+    let word: Vec<String> = vec!["a", "b", "d"].into_iter().map(|x| x.to_string()).collect();
+    assert!(automaton.recognise_lattice(lexicon.to_lattice(&word)).next().is_none());
+}
+
 #[test]
 fn test_coarse_to_fine_recogniser_correctness() {
     let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
@@ -81,6 +151,309 @@ fn test_coarse_to_fine_recogniser_correctness() {
     }
 }
 
+#[test]
+fn test_check_run_int() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let word: Vec<_> = String::from("aabccd").chars().map(|x| x.to_string()).collect();
+    let word_int: Vec<_> = word.iter().map(|t| automaton.terminal_to_int(t).unwrap()).collect();
+    let run: Vec<_> = automaton.recognise(word).next().unwrap().1
+        .iter()
+        .map(|t| automaton.transition_to_int(&t).unwrap())
+        .collect();
+
+    assert!(automaton.check_run_int(&word_int, &run).is_ok());
+    // the prefix of the run can be applied, but does not end in a terminal configuration
+    assert_eq!(Err(run.len() - 1), automaton.check_run_int(&word_int, &run[..run.len() - 1]));
+    // the run reads another word
+    assert!(automaton.check_run_int(&word_int[1..], &run).is_err());
+
+    // the fine runs of a single candidate per transition are checked in the same way
+    let fine_runs = |word_int: &[usize], run: &[Transition<_, _, _>]| FineRuns::new(
+        run.iter().map(|t| vec![t.clone()]).collect(),
+        Configuration { word: word_int.to_vec(), storage: automaton.initial_int(), weight: LogDomain::new(1.0).unwrap() },
+        TreeStackAutomaton::<PosState<PMCFGRule<String, String, LogDomain<f64>>>, String, LogDomain<f64>>::is_terminal,
+    );
+    let configurations: Vec<_> = fine_runs(&word_int, &run).map(|(c, _)| c).collect();
+    assert_eq!(automaton.check_run_int(&word_int, &run), Ok(configurations));
+    let mut prefix_runs = fine_runs(&word_int, &run[..run.len() - 1]);
+    assert!(prefix_runs.next().is_none());
+    assert_eq!(Some(run.len() - 1), prefix_runs.failed_transition());
+    let mut other_word_runs = fine_runs(&word_int[1..], &run);
+    assert!(other_word_runs.next().is_none());
+    assert_eq!(automaton.check_run_int(&word_int[1..], &run).err(), other_word_runs.failed_transition());
+}
+
+#[test]
+fn test_pmcfg_astar() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let word: Vec<_> = String::from("aabccd").chars().map(|x| x.to_string()).collect();
+
+    let best = automaton.recognise(word.clone()).next().unwrap();
+    let astar = automaton.recognise_astar(word, SearchOptions::default()).next().unwrap();
+    assert_eq!(best.0.weight, astar.0.weight);
+    assert_eq!(best.1, astar.1);
+
+    let invalid: Vec<_> = String::from("abd").chars().map(|x| x.to_string()).collect();
+    assert_eq!(None, automaton.recognise_astar(invalid, SearchOptions::default()).next());
+}
+
+#[test]
+fn test_unapproximate_run_lazily() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let (coarse_automaton, instance) = TTSElement::new().approximate_automaton(&automaton);
+    let word: Vec<_> = String::from("aabccd").chars().map(|x| x.to_string()).collect();
+    let word_int: Vec<_> = word.iter().map(|t| automaton.terminal_to_int(t).unwrap()).collect();
+
+    for (_, coarse_run) in coarse_automaton.recognise(word).take(10) {
+        let mut eager: Vec<_> = instance
+            .unapproximate_run(coarse_run.clone())
+            .into_iter()
+            .flat_map(|run| {
+                let run_int: Vec<_> = run.iter().map(|t| automaton.transition_to_int(&t).unwrap()).collect();
+                let configurations = automaton.check_run_int(&word_int, &run_int).unwrap_or(Vec::new());
+                configurations
+                    .into_iter()
+                    .map(|c| automaton.item_map(&(c, Pushdown::from(&run_int[..]))))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let mut lazy: Vec<_> = instance
+            .unapproximate_run_lazily(coarse_run, &automaton, word_int.clone())
+            .map(|item| automaton.item_map(&item))
+            .collect();
+
+        // the runs are enumerated in the order of descending weight
+        for i in 1..lazy.len() {
+            assert!(lazy[i - 1].0.weight >= lazy[i].0.weight);
+        }
+
+        eager.sort();
+        lazy.sort();
+        assert_eq!(eager, lazy);
+    }
+}
+
+#[test]
+fn test_approximation_report() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let (coarse_automaton, instance) = TTSElement::new().approximate_automaton(&automaton);
+    let corpus: Vec<Vec<String>> = vec!["aabccd", "abcd", "abc"]
+        .into_iter()
+        .map(|w| w.chars().map(|x| x.to_string()).collect())
+        .collect();
+
+    let report = instance.report(&automaton, &coarse_automaton, &corpus, 10);
+
+    assert_eq!(Automaton::transitions(&automaton).count(), report.fine_transitions);
+    assert!(report.coarse_transitions <= report.fine_transitions);
+    assert!(report.max_fine_transitions >= 1);
+
+    // the same statistics, obtained by unapproximating each coarse parse eagerly
+    let mut parses = 0;
+    let mut invalid = 0;
+    for sentence in &corpus {
+        let word_int: Vec<_> = sentence.iter().map(|t| automaton.terminal_to_int(t).unwrap()).collect();
+        for (_, coarse_run) in coarse_automaton.recognise(sentence.clone()).take(10) {
+            parses += 1;
+            let valid = instance
+                .unapproximate_run(coarse_run)
+                .into_iter()
+                .any(|run| {
+                    let run: Vec<_> = run.iter().map(|t| automaton.transition_to_int(&t).unwrap()).collect();
+                    automaton.check_run_int(&word_int, &run).is_ok()
+                });
+            if !valid {
+                invalid += 1;
+            }
+        }
+    }
+    assert!(parses > 0);
+    assert_eq!(parses, report.coarse_parses);
+    assert_eq!(invalid, report.invalid_parses);
+}
+
+#[test]
+fn test_chart_pruning_recogniser_correctness() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let (coarse_automaton, instance) = TTSElement::new().approximate_automaton(&automaton);
+    let recogniser = ChartPruningRecogniser::new(
+        automaton.clone(), coarse_automaton, instance, LogDomain::new(0.0).unwrap(), 10000
+    );
+
+    let inputs = vec![
+        "aabccd",
+        "aaabcccd",
+        "abccd",
+        "abbcd",
+    ];
+
+    for input in inputs {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        assert_eq!(
+            automaton.recognise(word.clone()).next(),
+            recogniser.recognise(word).next()
+        );
+    }
+
+    // the fine runs only apply transitions of the chart at the respective positions
+    let word: Vec<_> = String::from("aabccd").chars().map(|x| x.to_string()).collect();
+    let chart = recogniser.chart(word.clone());
+    let run = recogniser.recognise(word).next().unwrap().1;
+    let mut position = 0;
+    for transition in run.iter() {
+        assert!(chart[&position].contains(&transition));
+        position += transition.word.len();
+    }
+}
+
+#[test]
+fn test_chart_pruning_threshold() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let (coarse_automaton, instance) = TTSElement::new().approximate_automaton(&automaton);
+    let unpruned = ChartPruningRecogniser::new(
+        automaton.clone(), coarse_automaton, instance, LogDomain::new(0.0).unwrap(), 10000
+    );
+    let (coarse_automaton, instance) = TTSElement::new().approximate_automaton(&automaton);
+    let pruned = ChartPruningRecogniser::new(
+        automaton.clone(), coarse_automaton, instance, LogDomain::new(0.01).unwrap(), 10000
+    );
+
+    // dead ends of the coarse automaton have a posterior of zero
+    let word: Vec<_> = String::from("aabccd").chars().map(|x| x.to_string()).collect();
+    let posteriors = unpruned.coarse_posteriors(word.clone());
+    assert!(posteriors.values().any(|&p| p < LogDomain::new(0.01).unwrap()));
+
+    let size = |recogniser: &ChartPruningRecogniser<_, _, _, _, _>| -> usize {
+        recogniser.chart(word.clone()).values().map(|transitions| transitions.len()).sum()
+    };
+    assert!(size(&pruned) < size(&unpruned));
+    assert_eq!(
+        automaton.recognise(word.clone()).next(),
+        pruned.recognise(word).next()
+    );
+}
+
+#[test]
+fn test_compose_correctness() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let rel: EquivalenceRelation<String, String> = "0 [A, B]\n1 *".parse().unwrap();
+    let mapping = |ps: &PosState<_>| ps
+        .map(|r: &PMCFGRule<_, _, _>| r.map_nonterminals(|nt| rel.project(nt)));
+
+    // the composed instance maps the coarsest transitions directly to the finest ones
+    let composition = Compose::new(TTSElement::new(), RlbElement::new(&mapping));
+    let (coarse_automaton, instance) = Compose::new(TTSElement::new(), RlbElement::new(&mapping))
+        .approximate_automaton(&automaton);
+    for coarse_transition in coarse_automaton.transitions() {
+        let fine_transitions = instance.unapproximate_transition(&coarse_transition);
+        assert!(!fine_transitions.is_empty());
+        for fine_transition in fine_transitions {
+            assert_eq!(
+                coarse_transition.instruction,
+                ApproximationStrategy::<String, LogDomain<f64>>::approximate_instruction(
+                    &composition, &fine_transition.instruction
+                )
+            );
+        }
+    }
+
+    let recogniser = coarse_to_fine_recogniser!(automaton.clone(); composition);
+    let inputs = vec![
+        "aabccd",
+        "aaabcccd",
+        "abccd",
+        "abbcd",
+    ];
+
+    for input in inputs {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        assert_eq!(
+            automaton.recognise(word.clone()).next(),
+            recogniser.recognise(word).next()
+        );
+    }
+}
+
+#[test]
+fn test_relabel_tree_stack_correctness() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let rel: EquivalenceRelation<String, String> = "N [A, B]\nR *".parse().unwrap();
+    let mapping = |ps: &PosState<PMCFGRule<String, String, LogDomain<f64>>>| ps
+        .map(|r| rel.project(&r.head));
+    let rlb = TreeStackRlbElement::new(&mapping);
+    let (relabelled_automaton, _) = rlb.approximate_automaton(&automaton);
+
+    let true_positives_and_true_negatives = vec![
+        "",
+        "abcd",
+        "aabccd",
+        "abbcdd",
+        "acbd",
+        "aabccdd",
+    ];
+
+    for input in true_positives_and_true_negatives {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        assert_eq!(
+            automaton.recognise(word.clone()).next().is_some(),
+            relabelled_automaton.recognise(word).next().is_some()
+        );
+    }
+
+    // the components of `A` and `B` are no longer told apart
+    let false_positives = vec![
+        "abc",
+        "bacd",
+        "aabbccd",
+        "baadcc",
+    ];
+
+    for input in false_positives {
+        let word: Vec<_> = String::from(input).chars().map(|x| x.to_string()).collect();
+        assert!(automaton.recognise(word.clone()).next().is_none());
+        assert!(relabelled_automaton.recognise(word).next().is_some());
+    }
+}
+
+#[test]
+fn test_split_components_correctness() {
+    let grammar = pmcfg_from_file("examples/example.pmcfg");
+    let automaton = TreeStackAutomaton::from(grammar.clone());
+    let (cfg, rule_map) = split_components(&grammar);
+    let cfg_automaton = PushDownAutomaton::from(cfg);
+
+    let word: Vec<_> = String::from("aacc").chars().map(|x| x.to_string()).collect();
+    let coarse_parse = cfg_automaton.recognise(word.clone()).next().unwrap();
+    let coarse_rules: Vec<_> = rustomata::push_down_automaton::to_derivation(&coarse_parse.1)
+        .values()
+        .cloned()
+        .collect();
+    let restricted = restrict(&grammar, &rule_map, &coarse_rules);
+    assert!(restricted.rules.len() < grammar.rules.len());
+    assert!(TreeStackAutomaton::from(restricted).recognise(word).next().is_some());
+
+    let word: Vec<_> = String::from("abccd").chars().map(|x| x.to_string()).collect();
+    assert!(automaton.recognise(word.clone()).next().is_none());
+    assert!(cfg_automaton.recognise(word).next().is_some());
+}
+
+#[test]
+fn test_split_components_labels() {
+    let grammar: PMCFG<String, String, LogDomain<f64>> = "initial: [S]\n\
+                                                          S → [[Var 0 0, Var 1 0, Var 0 1]] (A, A_1)\n\
+                                                          A → [[T a], [T c]] () # 0.5\n\
+                                                          A_1 → [[T b]] ()".parse().unwrap();
+    let (cfg, _) = split_components(&grammar);
+
+    assert!(cfg.rules.contains(&"S → [Nt A__1, Nt A_1, Nt A__2]".parse().unwrap()));
+    assert!(cfg.rules.contains(&"A_1 → [T b]".parse().unwrap()));
+    let weights: Vec<_> = cfg.rules.iter().filter(|r| r.head.starts_with("A__")).map(|r| r.weight.clone()).collect();
+    assert_eq!(vec![LogDomain::new(0.5).unwrap(), LogDomain::new(1.0).unwrap()], weights);
+
+    let word: Vec<_> = String::from("abc").chars().map(|x| x.to_string()).collect();
+    assert!(PushDownAutomaton::from(cfg).recognise(word).next().is_some());
+}
+
 #[test]
 fn test_tts_correctness() {
     let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
@@ -288,3 +661,42 @@ fn test_pmcfg_recognise_illegal_terminal_symbols() {
         assert!(automaton.recognise(illegal_word).next().is_none());
     }
 }
+
+#[test]
+fn test_incremental_recogniser() {
+    let automaton = TreeStackAutomaton::from(pmcfg_from_file("examples/example.pmcfg"));
+    let mut recogniser = IncrementalRecogniser::new(&automaton);
+
+    assert!(recogniser.is_accepting());
+
+    let control_acceptances = vec![
+        ("a", false),
+        ("a", false),
+        ("b", false),
+        ("c", false),
+        ("c", false),
+        ("d", true),
+        ("d", false),
+    ];
+
+    let mut word = Vec::new();
+    for (token, control_acceptance) in control_acceptances {
+        word.push(token.to_string());
+        recogniser.push_token(token.to_string());
+        assert_eq!(control_acceptance, recogniser.is_accepting());
+
+        let parses = recogniser.best_parses(1);
+        let control_parses: Vec<_> = automaton.recognise(word.clone()).take(1).collect();
+        assert_eq!(control_parses.len(), parses.len());
+        for (control_parse, parse) in control_parses.into_iter().zip(parses) {
+            assert_eq!(control_parse.0.weight, parse.0.weight);
+            assert_eq!(control_parse.0.storage, parse.0.storage);
+        }
+    }
+
+    recogniser.reset();
+    for token in vec!["b", "d"] {
+        recogniser.push_token(token.to_string());
+    }
+    assert!(recogniser.is_accepting());
+}